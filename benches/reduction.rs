@@ -0,0 +1,53 @@
+//! Compares the reduction strategies on a fixed modulus
+//!
+//! Run with `cargo bench`.
+
+extern crate mod_exp;
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use mod_exp::{mod_exp, mod_exp_crt, multi_mod_exp, Barrett, Montgomery, WideningMulMod};
+
+const ITERATIONS: u64 = 20_000;
+
+fn time<F: FnMut(u64) -> u64>(name: &str, mut f: F) {
+    let start = Instant::now();
+    let mut acc = 0u64;
+    for i in 0..ITERATIONS {
+        acc ^= f(black_box(i));
+    }
+    black_box(acc);
+    let elapsed = start.elapsed();
+    println!("{:<24} {:>10.1} ns/iter", name, elapsed.as_nanos() as f64 / ITERATIONS as f64);
+}
+
+fn main() {
+    let even = (1u64 << 62) + 2;
+    let odd = 18446744073709551557u64;
+    let exponent = u64::MAX - 12345;
+
+    time("mod_exp (even)", |i| mod_exp(i + 2, exponent, even));
+    let barrett = Barrett::new(even);
+    time("Barrett::pow_mod (even)", |i| barrett.pow_mod(i + 2, exponent));
+
+    time("mod_exp (odd)", |i| mod_exp(i + 2, exponent, odd));
+    let barrett = Barrett::new(odd);
+    time("Barrett::pow_mod (odd)", |i| barrett.pow_mod(i + 2, exponent));
+    let montgomery = Montgomery::new(odd);
+    time("Montgomery::pow (odd)", |i| montgomery.pow(i + 2, exponent));
+
+    // Two powers multiplied together, separately and with shared squarings
+    time("2 x mod_exp (odd)", |i| mod_exp(i + 2, exponent, odd).mul_mod(mod_exp(i + 3, exponent - 1, odd), odd));
+    time("multi_mod_exp (odd)", |i| multi_mod_exp(&[(i + 2, exponent), (i + 3, exponent - 1)], odd));
+    time("multi_mod_exp (even)", |i| multi_mod_exp(&[(i + 2, exponent), (i + 3, exponent - 1)], even));
+    let (m127, wide) = ((1u128 << 127) - 1, u128::MAX - 12345);
+    time("2 x mod_exp (2^127 - 1)", |i| mod_exp(i as u128 + 2, wide, m127).mul_mod(mod_exp(i as u128 + 3, wide - 1, m127), m127) as u64);
+    time("multi_mod_exp (2^127 - 1)", |i| multi_mod_exp(&[(i as u128 + 2, wide), (i as u128 + 3, wide - 1)], m127) as u64);
+
+    // An RSA-style modulus with two 64-bit prime factors
+    let (p, q) = (odd as u128, 18446744073709551533u128);
+    let (n, exponent) = (p * q, u128::MAX - 12345);
+    time("mod_exp (p * q)", |i| mod_exp(i as u128 + 2, exponent, n) as u64);
+    time("mod_exp_crt (p * q)", |i| mod_exp_crt(i as u128 + 2, exponent, &[(p, 1), (q, 1)]) as u64);
+}
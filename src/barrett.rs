@@ -0,0 +1,171 @@
+use error::ModExpError;
+use reduce::Reducer;
+use strategy::{pow_with, Strategy};
+use word::Word;
+
+/// Precomputed Barrett reduction context for a fixed modulus
+///
+/// The context stores `floor((2^2w - 1) / n)`, with `w` the bit width of `T`,
+/// so that each reduction is done with multiplications instead of a hardware
+/// division. Unlike [`Montgomery`](crate::Montgomery) this works for even
+/// moduli too, and values never leave their ordinary representation.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::{mod_exp, Barrett};
+///
+/// let ctx = Barrett::new(1u64 << 40);
+/// assert_eq!(ctx.mul_mod(3 << 39, 5), 1 << 39);
+/// assert_eq!(ctx.pow_mod(3, 1000), mod_exp(3, 1000, 1 << 40));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Barrett<T> {
+    modulus: T,
+    mu_lo: T,
+    mu_hi: T,
+}
+
+impl<T: Word> Barrett<T> {
+    /// Builds the context for `modulus`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the modulus is zero or one
+    pub fn new(modulus: T) -> Barrett<T> {
+        match Barrett::checked_new(modulus) {
+            Ok(ctx) => ctx,
+            Err(e) => panic!("Barrett::new: {}", e),
+        }
+    }
+
+    /// Builds the context for `modulus`, or reports why it can't be used
+    pub fn checked_new(modulus: T) -> Result<Barrett<T>, ModExpError> {
+        if modulus.is_zero() {
+            return Err(ModExpError::ZeroModulus);
+        }
+        if modulus == T::one() {
+            return Err(ModExpError::ModulusOne);
+        }
+
+        // Long division of the all-ones double word by the modulus: the high
+        // word divides directly, then the low word is shifted in bit by bit.
+        let max = T::max_value();
+        let mu_hi = max / modulus;
+        let mut rem = max % modulus;
+        let mut mu_lo = T::zero();
+        for _ in 0..T::BITS {
+            let (doubled, carry) = rem.overflowing_add(rem);
+            rem = doubled | T::one();
+            mu_lo = mu_lo << 1;
+            if carry || rem >= modulus {
+                rem = rem.wrapping_sub(modulus);
+                mu_lo = mu_lo | T::one();
+            }
+        }
+
+        Ok(Barrett { modulus, mu_lo, mu_hi })
+    }
+
+    /// The modulus this context reduces by
+    pub fn modulus(&self) -> T {
+        self.modulus
+    }
+
+    /// Computes `(a * b) % n`
+    #[inline]
+    pub fn mul_mod(&self, a: T, b: T) -> T {
+        let (lo, hi) = a.widening_mul(b);
+        self.reduce(lo, hi)
+    }
+
+    /// Computes `base^exponent mod n`
+    pub fn pow_mod(&self, base: T, exponent: T) -> T {
+        self.pow_mod_with(Strategy::Binary, base, exponent)
+    }
+
+    /// Like [`pow_mod`](Barrett::pow_mod), scanning the exponent with `strategy`
+    pub fn pow_mod_with(&self, strategy: Strategy, base: T, exponent: T) -> T {
+        pow_with(self, strategy, self.reduce(base, T::zero()), exponent)
+    }
+
+    /// Remainder of `hi * 2^w + lo` modulo n
+    fn reduce(&self, lo: T, hi: T) -> T {
+        let n = self.modulus;
+
+        // q = floor(x * mu / 2^2w), which falls short of x / n by at most two.
+        // Only the middle columns of the four word product are needed.
+        let (_, p00_hi) = lo.widening_mul(self.mu_lo);
+        let (p01_lo, p01_hi) = lo.widening_mul(self.mu_hi);
+        let (p10_lo, p10_hi) = hi.widening_mul(self.mu_lo);
+        let (p11_lo, p11_hi) = hi.widening_mul(self.mu_hi);
+
+        let (col1, ca) = p00_hi.overflowing_add(p01_lo);
+        let (_, cb) = col1.overflowing_add(p10_lo);
+        let carry = bit::<T>(ca) + bit(cb);
+
+        let (q0, c1) = p01_hi.overflowing_add(p10_hi);
+        let (q0, c2) = q0.overflowing_add(p11_lo);
+        let (q0, c3) = q0.overflowing_add(carry);
+        let q1 = p11_hi.wrapping_add(bit(c1)).wrapping_add(bit(c2)).wrapping_add(bit(c3));
+
+        // r = x - q * n, computed modulo 2^2w since the true value is below 3n
+        let (qn_lo, qn_hi) = q0.widening_mul(n);
+        let qn_hi = qn_hi.wrapping_add(q1.wrapping_mul(n));
+        let (mut r_lo, borrow) = lo.overflowing_sub(qn_lo);
+        let mut r_hi = hi.wrapping_sub(qn_hi).wrapping_sub(bit(borrow));
+
+        while !r_hi.is_zero() || r_lo >= n {
+            let (diff, borrow) = r_lo.overflowing_sub(n);
+            r_lo = diff;
+            r_hi = r_hi.wrapping_sub(bit(borrow));
+        }
+        r_lo
+    }
+}
+
+impl<T: Word> Reducer<T> for Barrett<T> {
+    #[inline]
+    fn one(&self) -> T {
+        T::one()
+    }
+
+    #[inline]
+    fn mul(&self, a: T, b: T) -> T {
+        self.mul_mod(a, b)
+    }
+}
+
+#[inline]
+fn bit<T: Word>(b: bool) -> T {
+    if b { T::one() } else { T::zero() }
+}
+
+#[cfg(test)] mod tests {
+    use super::Barrett;
+    use {mod_exp, ModExpError, WideningMulMod};
+
+    #[test]
+    fn test_matches_mod_exp() {
+        for m in 2..=255u8 {
+            let ctx = Barrett::new(m);
+            for base in 0..=255u8 {
+                assert_eq!(ctx.mul_mod(base, 255 - base), (base as u32 * (255 - base) as u32 % m as u32) as u8);
+                assert_eq!(ctx.pow_mod(base, 77), mod_exp(base, 77, m));
+            }
+        }
+
+        for &m in &[2u128, 1 << 64, u128::MAX - 1, (1 << 127) + 6, u128::MAX] {
+            let ctx = Barrett::new(m);
+            assert_eq!(ctx.mul_mod(u128::MAX, u128::MAX), u128::MAX.mul_mod(u128::MAX, m));
+            assert_eq!(ctx.pow_mod(u128::MAX - 3, 12345), mod_exp(u128::MAX - 3, 12345, m));
+        }
+    }
+
+    #[test]
+    fn test_checked_new() {
+        assert_eq!(Barrett::checked_new(0u64), Err(ModExpError::ZeroModulus));
+        assert_eq!(Barrett::checked_new(1u64), Err(ModExpError::ModulusOne));
+        assert!(Barrett::checked_new(10u64).is_ok());
+    }
+}
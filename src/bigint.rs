@@ -0,0 +1,304 @@
+//! Modular exponentiation for arbitrary-precision integers
+//!
+//! Enabled with the `bigint` feature. The functions here mirror
+//! [`mod_exp`](crate::mod_exp) and [`checked_mod_exp`](crate::checked_mod_exp),
+//! but take their arguments by reference and work with [`BigUint`] and
+//! [`BigInt`], the two types implementing the sealed [`BigInteger`] trait. The
+//! [`ModExp`] trait is implemented for both, so generic code can accept either
+//! these or the primitive integers.
+//!
+//! [`ModExp`]: crate::ModExp
+
+use std::ops::{Mul, Rem};
+use num::bigint::{BigInt, BigUint};
+use num::{Integer, One, Zero};
+
+use error::ModExpError;
+use {splitmix64, ModExp};
+
+/// An arbitrary-precision integer: [`BigUint`] or [`BigInt`]
+///
+/// The functions in this module only take these two types. The trait is
+/// sealed, since a fixed-width integer would overflow in the intermediate
+/// products; those go through [`checked_mod_exp`](crate::checked_mod_exp)
+/// instead.
+///
+/// ```compile_fail
+/// let result = mod_exp::bigint::mod_exp(&3u64, &2, &7);
+/// ```
+pub trait BigInteger: sealed::Sealed + Integer + Clone {
+    /// `self^exponent mod modulus` for normalized arguments
+    #[doc(hidden)]
+    fn modpow(&self, exponent: &Self, modulus: &Self) -> Self;
+}
+
+impl BigInteger for BigUint {
+    fn modpow(&self, exponent: &BigUint, modulus: &BigUint) -> BigUint {
+        BigUint::modpow(self, exponent, modulus)
+    }
+}
+
+impl BigInteger for BigInt {
+    fn modpow(&self, exponent: &BigInt, modulus: &BigInt) -> BigInt {
+        BigInt::modpow(self, exponent, modulus)
+    }
+}
+
+mod sealed {
+    use num::bigint::{BigInt, BigUint};
+
+    pub trait Sealed {}
+
+    impl Sealed for BigUint {}
+    impl Sealed for BigInt {}
+}
+
+/// Performs the exponentiation
+///
+/// # Examples
+///
+/// ```
+/// extern crate mod_exp;
+/// extern crate num;
+///
+/// use num::BigUint;
+///
+/// # fn main() {
+/// let modulus = (BigUint::from(1u32) << 521) - BigUint::from(1u32);
+/// let exponent = &modulus - BigUint::from(1u32);
+/// let result = mod_exp::bigint::mod_exp(&BigUint::from(3u32), &exponent, &modulus);
+/// assert_eq!(result, BigUint::from(1u32));
+/// # }
+/// ```
+///
+/// # Panics
+///
+/// Panics in the same cases as [`mod_exp`](crate::mod_exp): when the modulus is
+/// zero, one or negative, or when the exponent is negative and `base` has no
+/// inverse modulo `modulus`
+pub fn mod_exp<T>(base: &T, exponent: &T, modulus: &T) -> T where T: BigInteger, for<'a> &'a T: Mul<&'a T, Output=T> + Rem<&'a T, Output=T> {
+    match checked_mod_exp(base, exponent, modulus) {
+        Ok(result) => result,
+        Err(e) => panic!("mod_exp: {}", e),
+    }
+}
+
+/// Performs the exponentiation, reporting invalid input instead of panicking
+///
+/// After the arguments are checked and normalized, the work is done by num's
+/// own `modpow`, which uses Montgomery multiplication for odd moduli.
+pub fn checked_mod_exp<T>(base: &T, exponent: &T, modulus: &T) -> Result<T, ModExpError> where T: BigInteger, for<'a> &'a T: Mul<&'a T, Output=T> + Rem<&'a T, Output=T> {
+    let zero = T::zero();
+
+    if modulus.is_zero() {
+        return Err(ModExpError::ZeroModulus);
+    }
+    if *modulus < zero {
+        return Err(ModExpError::NegativeModulus);
+    }
+    if modulus.is_one() {
+        return Err(ModExpError::ModulusOne);
+    }
+
+    let (base, exponent) = if *exponent < zero {
+        match mod_inverse(base, modulus) {
+            Some(inverse) => (inverse, zero - exponent.clone()),
+            None => return Err(ModExpError::NotInvertible),
+        }
+    } else {
+        let base = base % modulus;
+        if base < zero { (base + modulus.clone(), exponent.clone()) } else { (base, exponent.clone()) }
+    };
+
+    // The base is in [0, modulus) and the exponent non-negative, so the sign
+    // rules of BigInt::modpow never come into play
+    Ok(base.modpow(&exponent, modulus))
+}
+
+/// Computes the inverse of `a` modulo `modulus`
+///
+/// The arbitrary-precision counterpart of
+/// [`mod_inverse`](crate::mod_inverse): returns the `x` in `[0, modulus)` with
+/// `a * x == 1 (mod modulus)`, or `None` if there isn't one.
+pub fn mod_inverse<T>(a: &T, modulus: &T) -> Option<T> where T: BigInteger, for<'a> &'a T: Mul<&'a T, Output=T> + Rem<&'a T, Output=T> {
+    let zero = T::zero();
+    if *modulus <= zero {
+        return None;
+    }
+
+    let mut a = a % modulus;
+    if a < zero {
+        a = a + modulus.clone();
+    }
+
+    // Invariant: s_i * a == r_i (mod modulus)
+    let (mut r0, mut r1) = (modulus.clone(), a);
+    let (mut s0, mut s1) = (zero.clone(), &T::one() % modulus);
+
+    while !r1.is_zero() {
+        let (q, r) = r0.div_rem(&r1);
+        r0 = r1;
+        r1 = r;
+        let qs = &(&q * &s1) % modulus;
+        let s = if s0 >= qs { s0 - qs } else { s0 + (modulus.clone() - qs) };
+        s0 = s1;
+        s1 = s;
+    }
+
+    if r0.is_one() { Some(s0) } else { None }
+}
+
+/// Tests whether `n` is probably prime, with `rounds` rounds of Miller–Rabin
+///
+/// Small factors are removed by trial division first. The first round always
+/// uses the witness 2, and runs even when `rounds` is zero; the rest come
+/// from a pseudo-random sequence seeded by `n` itself, so the answer for a
+/// given `n` and `rounds` never changes. A prime is always reported as prime,
+/// and a composite survives each round with probability at most 1/4 for a
+/// randomly chosen witness. The sequence is not a cryptographic source, so
+/// don't rely on it alone for input chosen by an adversary.
+///
+/// # Examples
+///
+/// ```
+/// extern crate mod_exp;
+/// extern crate num;
+///
+/// use num::BigUint;
+///
+/// # fn main() {
+/// let m127 = (BigUint::from(1u32) << 127) - BigUint::from(1u32);
+/// assert!(mod_exp::bigint::is_probable_prime(&m127, 20));
+/// assert!(!mod_exp::bigint::is_probable_prime(&(&m127 * &m127), 20));
+/// # }
+/// ```
+pub fn is_probable_prime(n: &BigUint, rounds: usize) -> bool {
+    if let Some(answer) = trial_division(n) {
+        return answer;
+    }
+
+    let two = BigUint::from(2u32);
+    let mut state = n.to_u32_digits().iter().fold(0u64, |h, &digit| splitmix64(&mut (h ^ digit as u64)));
+    let bytes = n.bits().div_ceil(8);
+    let witness_range = n - 3u32;
+    (0..rounds.max(1)).all(|round| {
+        let a = if round == 0 {
+            two.clone()
+        } else {
+            let random: Vec<u8> = (0..bytes).map(|_| splitmix64(&mut state) as u8).collect();
+            BigUint::from_bytes_le(&random) % &witness_range + &two
+        };
+        is_strong_probable_prime(n, &a)
+    })
+}
+
+/// Decides `n` outright if it is below `53^2` or has a prime factor below 53
+pub(crate) fn trial_division(n: &BigUint) -> Option<bool> {
+    for &p in &[2u32, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47] {
+        let p = BigUint::from(p);
+        if *n == p {
+            return Some(true);
+        }
+        if (n % &p).is_zero() {
+            return Some(false);
+        }
+    }
+    if *n < BigUint::from(53u32 * 53) {
+        return Some(*n > BigUint::one());
+    }
+    None
+}
+
+/// One Miller–Rabin round with the witness `a`, for an odd `n > 2`
+pub(crate) fn is_strong_probable_prime(n: &BigUint, a: &BigUint) -> bool {
+    let one = BigUint::one();
+    let n_minus_one = n - &one;
+    let mut s = 0;
+    let mut d = n_minus_one.clone();
+    while d.is_even() {
+        d >>= 1;
+        s += 1;
+    }
+
+    let mut x = mod_exp(a, &d, n);
+    if x == one || x == n_minus_one {
+        return true;
+    }
+    for _ in 1..s {
+        x = &(&x * &x) % n;
+        if x == n_minus_one {
+            return true;
+        }
+        if x == one {
+            return false;
+        }
+    }
+    false
+}
+
+impl ModExp for BigUint {
+    fn checked_mod_exp(&self, exponent: &BigUint, modulus: &BigUint) -> Result<BigUint, ModExpError> {
+        checked_mod_exp(self, exponent, modulus)
+    }
+}
+
+impl ModExp for BigInt {
+    fn checked_mod_exp(&self, exponent: &BigInt, modulus: &BigInt) -> Result<BigInt, ModExpError> {
+        checked_mod_exp(self, exponent, modulus)
+    }
+}
+
+#[cfg(test)] mod tests {
+    use num::bigint::{BigInt, BigUint};
+
+    use super::{checked_mod_exp, is_probable_prime, mod_inverse};
+    use {is_prime, ModExp, ModExpError};
+
+    #[test]
+    fn test_matches_primitive() {
+        for base in -30i64..30 {
+            for &(exponent, modulus) in &[(0i64, 7i64), (13, 497), (77, 1000), (255, 1 << 40), (-5, 31), (-77, 1000)] {
+                let expected = ::checked_mod_exp(base, exponent, modulus).map(BigInt::from);
+                let big = checked_mod_exp(&BigInt::from(base), &BigInt::from(exponent), &BigInt::from(modulus));
+                assert_eq!(big, expected);
+            }
+        }
+
+        assert_eq!(checked_mod_exp(&BigInt::from(2), &BigInt::from(3), &BigInt::from(-5)), Err(ModExpError::NegativeModulus));
+        assert_eq!(checked_mod_exp(&BigInt::from(2), &BigInt::from(-3), &BigInt::from(6)), Err(ModExpError::NotInvertible));
+        assert_eq!(mod_inverse(&BigUint::from(3u32), &BigUint::from(11u32)), Some(BigUint::from(4u32)));
+        assert_eq!(checked_mod_exp(&BigUint::from(2u32), &BigUint::from(3u32), &BigUint::from(1u32)), Err(ModExpError::ModulusOne));
+    }
+
+    #[test]
+    fn test_large_modulus() {
+        // 2^607 - 1 is prime, 2^600 + 1 is not
+        let one = BigUint::from(1u32);
+        let base = BigUint::parse_bytes(b"123456789abcdef0123456789abcdef", 16).unwrap();
+        let prime = (&one << 607) - &one;
+        assert_eq!(base.mod_exp(&(&prime - &one), &prime), one);
+
+        let composite = (&one << 600) + &one;
+        let exponent = &composite - &one;
+        assert_eq!(base.mod_exp(&exponent, &composite), base.modpow(&exponent, &composite));
+    }
+
+    #[test]
+    fn test_is_probable_prime() {
+        for n in 0..5000u64 {
+            assert_eq!(is_probable_prime(&BigUint::from(n), 4), is_prime(n), "{}", n);
+        }
+        for &n in &[3215031751u64, 3825123056546413051, 18446744073709551557] {
+            assert_eq!(is_probable_prime(&BigUint::from(n), 10), is_prime(n), "{}", n);
+        }
+
+        // 2^521 - 1 is prime; 2^523 - 1 is not
+        let one = BigUint::from(1u32);
+        assert!(is_probable_prime(&((&one << 521) - &one), 10));
+        assert!(!is_probable_prime(&((&one << 523) - &one), 10));
+
+        // Zero rounds still runs the base 2 round
+        assert!(!is_probable_prime(&BigUint::from(53u32 * 59), 0));
+        assert!(is_probable_prime(&BigUint::from(18446744073709551557u64), 0));
+    }
+}
@@ -0,0 +1,320 @@
+//! The Baillie–PSW probable prime test
+//!
+//! A number passes if it is a strong probable prime to base 2 and a strong
+//! Lucas probable prime with the parameters chosen by Selfridge's method A:
+//! `D` is the first of `5, -7, 9, -11, ...` with Jacobi symbol `(D/n) = -1`,
+//! `P = 1` and `Q = (1 - D) / 4`. The two tests fail on very different
+//! composites, and no number passing both while being composite is known,
+//! though none has been ruled out above `2^64`.
+//!
+//! [`is_probable_prime`] works on the primitive unsigned integers, inside a
+//! [`Montgomery`] context; with the `bigint` feature, `is_probable_prime_big`
+//! does the same for `BigUint`.
+
+#[cfg(feature = "bigint")]
+use num::{BigUint, Integer, One, Zero};
+
+#[cfg(feature = "bigint")]
+use bigint;
+use montgomery::Montgomery;
+use prime::{is_strong_probable_prime, trial_division};
+use reduce::Reducer;
+use symbol::jacobi;
+use word::{isqrt, Word};
+
+/// Tests whether `n` is a Baillie–PSW probable prime
+///
+/// The answer is exact below `2^64`, where every composite passing the test
+/// would have been found by now.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::bpsw;
+///
+/// assert!(bpsw::is_probable_prime(u128::MAX - 158));
+/// // 3825123056546413051 is a strong pseudoprime to every prime base up to 23
+/// assert!(!bpsw::is_probable_prime(3825123056546413051u64));
+/// ```
+pub fn is_probable_prime<T: Word>(n: T) -> bool {
+    if let Some(answer) = trial_division(n) {
+        return answer;
+    }
+
+    let ctx = Montgomery::new(n);
+    let n_minus_one = n - T::one();
+    let s = n_minus_one.trailing_zeros() as usize;
+    let two = T::one() + T::one();
+    is_strong_probable_prime(&ctx, two, n_minus_one >> s, s) && is_strong_lucas_probable_prime(&ctx)
+}
+
+/// Tests whether `n` is a Baillie–PSW probable prime
+///
+/// The arbitrary-precision counterpart of [`is_probable_prime`].
+///
+/// # Examples
+///
+/// ```
+/// extern crate mod_exp;
+/// extern crate num;
+///
+/// use num::BigUint;
+///
+/// # fn main() {
+/// let m607 = (BigUint::from(1u32) << 607) - BigUint::from(1u32);
+/// assert!(mod_exp::bpsw::is_probable_prime_big(&m607));
+/// assert!(!mod_exp::bpsw::is_probable_prime_big(&(&m607 * &m607)));
+/// # }
+/// ```
+#[cfg(feature = "bigint")]
+pub fn is_probable_prime_big(n: &BigUint) -> bool {
+    if let Some(answer) = bigint::trial_division(n) {
+        return answer;
+    }
+    bigint::is_strong_probable_prime(n, &BigUint::from(2u32)) && is_strong_lucas_probable_prime_big(n)
+}
+
+/// Selfridge's method A: the first `D` in `5, -7, 9, -11, ...` with
+/// `(D/n) = -1`, as `(|D|, negative)`, or `None` if `n` is a perfect square and
+/// there is no such `D`
+///
+/// `jacobi(|D|, n)` is the Jacobi symbol of the absolute value.
+fn selfridge<J: FnMut(u32) -> i32>(n_mod_4: u32, mut jacobi: J, is_square: bool) -> Option<(u32, bool)> {
+    if is_square {
+        return None;
+    }
+    let mut d = 5;
+    let mut negative = false;
+    loop {
+        // (-1/n) is 1 for n == 1 (mod 4) and -1 for n == 3 (mod 4)
+        let sign = if negative && n_mod_4 == 3 { -1 } else { 1 };
+        if sign * jacobi(d) == -1 {
+            return Some((d, negative));
+        }
+        d += 2;
+        negative = !negative;
+    }
+}
+
+/// The strong Lucas probable prime test, for an odd `n` with no small factors
+pub(crate) fn is_strong_lucas_probable_prime<T: Word>(ctx: &Montgomery<T>) -> bool {
+    let n = ctx.modulus();
+    let n_mod_4 = (n & T::from(3).unwrap()).to_u32().unwrap();
+    let (d, negative) = match selfridge(n_mod_4, |d| jacobi(T::from(d).unwrap(), n), isqrt(n) * isqrt(n) == n) {
+        Some(d) => d,
+        None => return false,
+    };
+
+    let add = |a: T, b: T| {
+        let (sum, carry) = a.overflowing_add(b);
+        if carry || sum >= n { sum.wrapping_sub(n) } else { sum }
+    };
+    let sub = |a: T, b: T| {
+        let (diff, borrow) = a.overflowing_sub(b);
+        if borrow { diff.wrapping_add(n) } else { diff }
+    };
+    // x / 2 mod n, for odd n
+    let half = |x: T| if x & T::one() == T::one() { (x >> 1) + (n >> 1) + T::one() } else { x >> 1 };
+
+    // Everything below is in Montgomery form, where addition, subtraction
+    // and halving work unchanged.
+    let one = ctx.one();
+    let magnitude = ctx.to_montgomery(T::from(d).unwrap());
+    let d_mont = if negative { sub(T::zero(), magnitude) } else { magnitude };
+    // Q = (1 - D) / 4
+    let q = half(half(sub(one, d_mont)));
+
+    // n + 1 = k * 2^s with k odd, computed without overflowing
+    let m = (n >> 1) + T::one();
+    let s = m.trailing_zeros() as usize + 1;
+    let k = m >> (s - 1);
+
+    // U_j, V_j and Q^j for j the leading bits of k, starting from j = 1
+    let (mut u, mut v, mut qj) = (one, one, q);
+    let bits = T::BITS - k.leading_zeros();
+    for i in (0..bits - 1).rev() {
+        u = ctx.mul(u, v);
+        v = sub(ctx.mul(v, v), add(qj, qj));
+        qj = ctx.mul(qj, qj);
+        if (k >> i as usize) & T::one() == T::one() {
+            let (pu, dv) = (u, ctx.mul(d_mont, u));
+            u = half(add(pu, v));
+            v = half(add(dv, v));
+            qj = ctx.mul(qj, q);
+        }
+    }
+
+    if u.is_zero() || v.is_zero() {
+        return true;
+    }
+    for _ in 1..s {
+        v = sub(ctx.mul(v, v), add(qj, qj));
+        qj = ctx.mul(qj, qj);
+        if v.is_zero() {
+            return true;
+        }
+    }
+    false
+}
+
+/// The strong Lucas probable prime test, for an odd `n` with no small factors
+#[cfg(feature = "bigint")]
+fn is_strong_lucas_probable_prime_big(n: &BigUint) -> bool {
+    let n_mod_4 = (n % 4u32).to_u32_digits().first().cloned().unwrap_or(0);
+    let root = n.sqrt();
+    let (d, negative) = match selfridge(n_mod_4, |d| jacobi_big(&(BigUint::from(d) % n), n), &root * &root == *n) {
+        Some(d) => d,
+        None => return false,
+    };
+
+    let add = |a: &BigUint, b: &BigUint| (a + b) % n;
+    let sub = |a: &BigUint, b: &BigUint| if a >= b { a - b } else { n - b + a };
+    let mul = |a: &BigUint, b: &BigUint| (a * b) % n;
+    let half = |x: BigUint| if x.is_odd() { (x + n) >> 1 } else { x >> 1 };
+
+    let one = BigUint::one();
+    let magnitude = BigUint::from(d) % n;
+    let d = if negative { sub(&BigUint::zero(), &magnitude) } else { magnitude };
+    let q = half(half(sub(&one, &d)));
+
+    let mut k = n + 1u32;
+    let mut s = 0;
+    while k.is_even() {
+        k >>= 1;
+        s += 1;
+    }
+
+    let (mut u, mut v, mut qj) = (one.clone(), one, q.clone());
+    for i in (0..k.bits() - 1).rev() {
+        u = mul(&u, &v);
+        v = sub(&mul(&v, &v), &add(&qj, &qj));
+        qj = mul(&qj, &qj);
+        if (&k >> i).is_odd() {
+            let dv = mul(&d, &u);
+            u = half(add(&u, &v));
+            v = half(add(&dv, &v));
+            qj = mul(&qj, &q);
+        }
+    }
+
+    if u.is_zero() || v.is_zero() {
+        return true;
+    }
+    for _ in 1..s {
+        v = sub(&mul(&v, &v), &add(&qj, &qj));
+        qj = mul(&qj, &qj);
+        if v.is_zero() {
+            return true;
+        }
+    }
+    false
+}
+
+/// The Jacobi symbol `(a/n)` for odd `n`
+#[cfg(feature = "bigint")]
+fn jacobi_big(a: &BigUint, n: &BigUint) -> i32 {
+    let (mut a, mut n) = (a.clone(), n.clone());
+    let low = |x: &BigUint, mask: u32| (x % (mask + 1)).to_u32_digits().first().cloned().unwrap_or(0);
+    let mut result = 1;
+    while !a.is_zero() {
+        let mut twos = 0;
+        while a.is_even() {
+            a >>= 1;
+            twos += 1;
+        }
+        let n_mod_8 = low(&n, 7);
+        if twos % 2 == 1 && (n_mod_8 == 3 || n_mod_8 == 5) {
+            result = -result;
+        }
+        if low(&a, 3) == 3 && n_mod_8 % 4 == 3 {
+            result = -result;
+        }
+        let r = &n % &a;
+        n = a;
+        a = r;
+    }
+    if n.is_one() { result } else { 0 }
+}
+
+#[cfg(test)] mod tests {
+    use super::{is_probable_prime, is_strong_lucas_probable_prime};
+    use montgomery::Montgomery;
+    use prime::is_strong_probable_prime;
+    use word::isqrt;
+    use is_prime;
+
+    /// Strong pseudoprimes to base 2 (OEIS A001262)
+    const STRONG_PSP_2: [u64; 24] = [
+        2047, 3277, 4033, 4681, 8321, 15841, 29341, 42799, 49141, 52633, 65281, 74665,
+        80581, 85489, 88357, 90751, 104653, 130561, 196093, 220729, 233017, 252601, 253241, 256999,
+    ];
+
+    /// Strong Lucas pseudoprimes with Selfridge's parameters (OEIS A217255)
+    const STRONG_LUCAS_PSP: [u64; 24] = [
+        5459, 5777, 10877, 16109, 18971, 22499, 24569, 25199, 40309, 58519, 75077, 97439,
+        100127, 113573, 115639, 130139, 155819, 158399, 161027, 162133, 176399, 176471, 189419, 192509,
+    ];
+
+    fn passes_base_2(n: u64) -> bool {
+        let s = (n - 1).trailing_zeros() as usize;
+        is_strong_probable_prime(&Montgomery::new(n), 2, (n - 1) >> s, s)
+    }
+
+    #[test]
+    fn test_pseudoprime_lists() {
+        for &n in &STRONG_PSP_2 {
+            assert!(!is_prime(n), "{}", n);
+            assert!(passes_base_2(n), "{}", n);
+            assert!(!is_strong_lucas_probable_prime(&Montgomery::new(n)), "{}", n);
+            assert!(!is_probable_prime(n), "{}", n);
+        }
+        for &n in &STRONG_LUCAS_PSP {
+            assert!(!is_prime(n), "{}", n);
+            assert!(is_strong_lucas_probable_prime(&Montgomery::new(n)), "{}", n);
+            assert!(!passes_base_2(n), "{}", n);
+            assert!(!is_probable_prime(n), "{}", n);
+        }
+
+        // Below 260000 the lists above are complete
+        let composites = || (3..260000u64).step_by(2).filter(|&n| !is_prime(n));
+        let lucas: Vec<u64> = composites()
+            .filter(|&n| n <= 192509 && isqrt(n) * isqrt(n) != n && is_strong_lucas_probable_prime(&Montgomery::new(n)))
+            .collect();
+        assert_eq!(lucas, STRONG_LUCAS_PSP);
+        let base_2: Vec<u64> = composites().filter(|&n| n <= 256999 && passes_base_2(n)).collect();
+        assert_eq!(base_2, STRONG_PSP_2);
+    }
+
+    #[test]
+    fn test_matches_is_prime() {
+        for n in 0..100000u32 {
+            assert_eq!(is_probable_prime(n), is_prime(n), "{}", n);
+        }
+        for n in (u64::MAX - 20000)..=u64::MAX {
+            assert_eq!(is_probable_prime(n), is_prime(n), "{}", n);
+        }
+        for &n in &[3825123056546413051u64, 1194649, 12327121] {
+            assert_eq!(is_probable_prime(n), is_prime(n), "{}", n);
+        }
+        assert!(is_probable_prime((1u128 << 127) - 1));
+        assert!(!is_probable_prime(((1u128 << 61) - 1) * ((1 << 61) - 1)));
+        assert!(!is_probable_prime(318665857834031151167461u128));
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_bigint_matches_primitive() {
+        use num::BigUint;
+        use super::is_probable_prime_big;
+
+        for n in (0..30000u64).chain((u64::MAX - 3000)..=u64::MAX).chain(STRONG_PSP_2.iter().cloned()).chain(STRONG_LUCAS_PSP.iter().cloned()) {
+            assert_eq!(is_probable_prime_big(&BigUint::from(n)), is_probable_prime(n), "{}", n);
+        }
+
+        let one = BigUint::from(1u32);
+        assert!(is_probable_prime_big(&((&one << 521) - &one)));
+        assert!(!is_probable_prime_big(&((&one << 523) - &one)));
+        let square = BigUint::from((1u64 << 61) - 1) * BigUint::from((1u64 << 61) - 1);
+        assert!(!is_probable_prime_big(&square));
+    }
+}
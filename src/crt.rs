@@ -0,0 +1,227 @@
+use std::ops::Shr;
+use num::traits::{checked_pow, CheckedMul, Num, One, Zero};
+
+use error::ModExpError;
+use gcd::{gcd, mod_inverse, sub_mod};
+use wide::WideningMulMod;
+use {check_modulus, checked_mod_exp, invert_negative_exponent, rem_euclid};
+
+/// Solves the system of congruences `x == r (mod m)`, given as `(r, m)` pairs
+///
+/// The moduli need not be pairwise coprime. Returns `(x, lcm)`, with `lcm`
+/// the least common multiple of the moduli and `x` the unique solution in
+/// `[0, lcm)`, or `None` if the congruences contradict each other. An empty
+/// system is solved by `(0, 1)`.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::crt;
+///
+/// assert_eq!(crt(&[(2, 3), (3, 5), (2, 7)]), Some((23, 105)));
+/// assert_eq!(crt(&[(3, 4), (5, 6)]), Some((11, 12)));
+/// assert_eq!(crt(&[(1, 4), (2, 6)]), None);
+/// ```
+///
+/// # Panics
+///
+/// Panics whenever [`checked_crt`] would return an error
+pub fn crt<T>(congruences: &[(T, T)]) -> Option<(T, T)> where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod + CheckedMul {
+    match checked_crt(congruences) {
+        Ok(solution) => solution,
+        Err(e) => panic!("crt: {}", e),
+    }
+}
+
+#[allow(non_snake_case)]
+/// Solves the system of congruences, reporting invalid moduli instead of
+/// panicking
+///
+/// Fails with [`ModExpError::ZeroModulus`] or
+/// [`ModExpError::NegativeModulus`] for a modulus that isn't positive, and
+/// with [`ModExpError::Overflow`] when the least common multiple of the
+/// moduli doesn't fit in `T`. Contradictory congruences are `Ok(None)`.
+pub fn checked_crt<T>(congruences: &[(T, T)]) -> Result<Option<(T, T)>, ModExpError> where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod + CheckedMul {
+    let ONE: T = One::one();
+    let ZERO: T = Zero::zero();
+
+    for &(_, m) in congruences {
+        if m.is_zero() {
+            return Err(ModExpError::ZeroModulus);
+        }
+        if m < ZERO {
+            return Err(ModExpError::NegativeModulus);
+        }
+    }
+
+    let (mut x, mut lcm) = (ZERO, ONE);
+    for &(r, m) in congruences {
+        let r = rem_euclid(r, m);
+        let g = gcd(lcm, m);
+        let diff = sub_mod(r, x % m, m);
+        if !(diff % g).is_zero() {
+            return Ok(None);
+        }
+
+        // x + lcm * k solves the new congruence when
+        // (lcm / g) * k == diff / g (mod m / g)
+        let m_g = m / g;
+        let inverse = mod_inverse(lcm / g, m_g).unwrap();
+        let k = (diff / g).mul_mod(inverse, m_g);
+        let step = lcm;
+        lcm = match lcm.checked_mul(&m_g) {
+            Some(product) => product,
+            None => return Err(ModExpError::Overflow),
+        };
+        // k < m / g, so this stays below the new lcm
+        x = x + step * k;
+    }
+    Ok(Some((x, lcm)))
+}
+
+/// Computes `base^exponent mod n`, given the factorization of `n` as
+/// `(prime, exponent)` pairs
+///
+/// Exponentiates modulo each prime power separately and joins the results
+/// with [`crt`]. Each exponentiation works on numbers of a fraction of the
+/// size, and, when `base` is coprime to the prime, with the exponent reduced
+/// modulo `phi(q^e) = q^(e-1) * (q - 1)`. For an RSA-style `u128` modulus
+/// with two 64-bit primes, both exponentiations run in 64-bit arithmetic and
+/// the whole is several times faster than [`mod_exp`](crate::mod_exp) on `n`
+/// itself.
+///
+/// The factors must be distinct primes. A factor of one, or two that share a
+/// factor, is reported as [`ModExpError::InvalidFactorization`]; primality
+/// itself isn't checked, and the result is wrong for coprime composites.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::{mod_exp, mod_exp_crt};
+///
+/// let (p, q) = (4294967291u64, 4294967279);
+/// let n = p * q;
+/// assert_eq!(mod_exp_crt(65537, n - 2, &[(p, 1), (q, 1)]), mod_exp(65537, n - 2, n));
+/// assert_eq!(mod_exp_crt(-2i64, -3, &[(3, 2), (7, 1)]), mod_exp(-2, -3, 63));
+/// ```
+///
+/// # Panics
+///
+/// Panics whenever [`checked_mod_exp_crt`] would return an error
+pub fn mod_exp_crt<T>(base: T, exponent: T, factors: &[(T, u32)]) -> T where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod + CheckedMul {
+    match checked_mod_exp_crt(base, exponent, factors) {
+        Ok(result) => result,
+        Err(e) => panic!("mod_exp_crt: {}", e),
+    }
+}
+
+#[allow(non_snake_case)]
+/// Computes `base^exponent mod n` from the factorization of `n`, reporting
+/// invalid input instead of panicking
+///
+/// Fails with [`ModExpError::Overflow`] if `n` doesn't fit in `T`, with
+/// [`ModExpError::InvalidFactorization`] for factors that are one or not
+/// coprime, and otherwise in the same cases as [`checked_mod_exp`] would for
+/// `n`.
+pub fn checked_mod_exp_crt<T>(base: T, exponent: T, factors: &[(T, u32)]) -> Result<T, ModExpError> where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod + CheckedMul {
+    let ONE: T = One::one();
+    let ZERO: T = Zero::zero();
+
+    let mut n = ONE;
+    let mut prime_powers = Vec::with_capacity(factors.len());
+    for &(q, e) in factors {
+        if q <= ZERO {
+            return Err(if q.is_zero() { ModExpError::ZeroModulus } else { ModExpError::NegativeModulus });
+        }
+        let power = match checked_pow(q, e as usize) {
+            Some(power) => power,
+            None => return Err(ModExpError::Overflow),
+        };
+        n = match n.checked_mul(&power) {
+            Some(n) => n,
+            None => return Err(ModExpError::Overflow),
+        };
+        prime_powers.push((q, e, power));
+    }
+
+    for (i, &(q, e, _)) in prime_powers.iter().enumerate() {
+        if e > 0 && (q == ONE || prime_powers[..i].iter().any(|&(r, f, _)| f > 0 && !gcd(q, r).is_one())) {
+            return Err(ModExpError::InvalidFactorization);
+        }
+    }
+
+    check_modulus(n)?;
+    if exponent < ZERO {
+        let (inverse, rest) = invert_negative_exponent(base, exponent, n)?;
+        let rest = checked_mod_exp_crt(inverse, rest, factors)?;
+        return Ok(rest.mul_mod(inverse, n));
+    }
+
+    let mut congruences = Vec::with_capacity(prime_powers.len());
+    for (q, e, power) in prime_powers {
+        if e == 0 {
+            continue;
+        }
+        let base = rem_euclid(base, power);
+        // Euler's theorem, base^phi(q^e) == 1 (mod q^e), only holds for a
+        // base coprime to q
+        let exponent = if (base % q).is_zero() { exponent } else { exponent % (power / q * (q - ONE)) };
+        congruences.push((checked_mod_exp(base, exponent, power)?, power));
+    }
+
+    match checked_crt(&congruences)? {
+        Some((x, _)) => Ok(x),
+        None => Err(ModExpError::InvalidFactorization),
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::{checked_crt, checked_mod_exp_crt, crt, mod_exp_crt};
+    use {checked_mod_exp, mod_exp, ModExpError};
+
+    #[test]
+    fn test_crt_matches_brute_force() {
+        for m1 in 1..30i32 {
+            for m2 in 1..30 {
+                for r1 in -3..m1 {
+                    for r2 in [0, 1, m2 - 1, 7].iter().cloned() {
+                        let lcm = m1 * m2 / ::gcd::gcd(m1, m2);
+                        let expected = (0..lcm).find(|x| (x - r1).rem_euclid(m1) == 0 && (x - r2).rem_euclid(m2) == 0);
+                        assert_eq!(crt(&[(r1, m1), (r2, m2)]), expected.map(|x| (x, lcm)), "{} mod {}, {} mod {}", r1, m1, r2, m2);
+                    }
+                }
+            }
+        }
+
+        assert_eq!(crt::<u64>(&[]), Some((0, 1)));
+        assert_eq!(checked_crt(&[(1u64, 1 << 40), (1, (1 << 40) - 1), (1, 1 << 30 | 1)]), Err(ModExpError::Overflow));
+        assert_eq!(checked_crt(&[(1i8, 0)]), Err(ModExpError::ZeroModulus));
+        assert_eq!(checked_crt(&[(1i8, -3)]), Err(ModExpError::NegativeModulus));
+    }
+
+    #[test]
+    fn test_mod_exp_crt_matches_mod_exp() {
+        let factorizations: [&[(i64, u32)]; 5] = [&[(2, 3)], &[(2, 2), (3, 1), (5, 2)], &[(3, 4), (7, 1)], &[(11, 1), (13, 1)], &[(2, 1), (7, 2), (3, 0)]];
+        for factors in &factorizations {
+            let n = factors.iter().map(|&(q, e)| q.pow(e)).product();
+            for base in -30i64..30 {
+                for exponent in -10i64..40 {
+                    assert_eq!(checked_mod_exp_crt(base, exponent, factors), checked_mod_exp(base, exponent, n),
+                               "{}^{} mod {}", base, exponent, n);
+                }
+            }
+        }
+
+        let (p, q) = (18446744073709551557u128, 18446744073709551533);
+        let n = p * q;
+        for &(base, exponent) in &[(2u128, n - 1), (p, 12345), (n - 1, u128::MAX)] {
+            assert_eq!(mod_exp_crt(base, exponent, &[(p, 1), (q, 1)]), mod_exp(base, exponent, n));
+        }
+        assert_eq!(checked_mod_exp_crt(2u32, 3, &[]), Err(ModExpError::ModulusOne));
+        assert_eq!(checked_mod_exp_crt(2u32, 3, &[(65537, 2)]), Err(ModExpError::Overflow));
+        for factors in &[[(6i64, 1), (3, 1)], [(7, 1), (7, 2)], [(1, 1), (5, 1)]] {
+            assert_eq!(checked_mod_exp_crt(5, 5, factors), Err(ModExpError::InvalidFactorization), "{:?}", factors);
+        }
+        assert_eq!(mod_exp_crt(5i64, 5, &[(7, 1), (7, 0), (1, 0)]), mod_exp(5, 5, 7));
+    }
+}
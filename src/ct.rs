@@ -0,0 +1,176 @@
+use error::ModExpError;
+use montgomery::Montgomery;
+use reduce::{Plain, Reducer};
+use word::Word;
+
+/// Performs the exponentiation in constant time with respect to `exponent`
+///
+/// Left-to-right square-and-multiply-always: every bit of the exponent's full
+/// width costs one squaring and one multiplication, and the product is kept or
+/// dropped with a mask.
+///
+/// # Guarantees
+///
+/// The loop runs exactly `T::BITS` iterations whatever the value of the
+/// exponent, and picks between intermediate values with masks instead of
+/// branches or table lookups. What that buys depends on the modulus and the
+/// integer type:
+///
+/// | modulus | `u8`..`u64`, `usize` | `u128` |
+/// |---------|----------------------|--------|
+/// | odd     | Montgomery multiplication on native multiplies: no secret-dependent branches or memory accesses | the same, with the 256 bit product built from 64 bit multiplies |
+/// | even    | `%` in the next wider type: hardware division, whose latency depends on its operands on many CPUs | software 256 bit reduction that branches on the product: **not** constant time |
+///
+/// In every case the modulus is treated as public; building the Montgomery
+/// context branches on it. The base is reduced with one `%` up front. These
+/// are source-level guarantees only: nothing stops the compiler from turning
+/// a mask back into a branch, so check the generated code before trusting it
+/// with real secrets.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::mod_exp_ct;
+///
+/// assert_eq!(mod_exp_ct(4u64, 13, 497), 445);
+/// assert_eq!(mod_exp_ct(5u32, 3, 16), 13);
+/// ```
+///
+/// # Panics
+///
+/// Panics if the modulus is zero or one
+pub fn mod_exp_ct<T: Word>(base: T, exponent: T, modulus: T) -> T {
+    check_modulus(modulus, "mod_exp_ct");
+
+    if modulus & T::one() == T::one() {
+        let ctx = Montgomery::new(modulus);
+        ctx.from_montgomery(multiply_always(&ConstantTime(ctx), ctx.to_montgomery(base), exponent))
+    } else {
+        multiply_always(&Plain { modulus }, base % modulus, exponent)
+    }
+}
+
+/// Performs the exponentiation in constant time with the Montgomery ladder
+///
+/// Keeps the pair `(x^k, x^(k+1))` and, for every bit of the exponent's full
+/// width, conditionally swaps it with a mask around one multiplication and one
+/// squaring. Has the same guarantees as [`mod_exp_ct`].
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::mod_exp_ct_ladder;
+///
+/// assert_eq!(mod_exp_ct_ladder(4u64, 13, 497), 445);
+/// ```
+///
+/// # Panics
+///
+/// Panics if the modulus is zero or one
+pub fn mod_exp_ct_ladder<T: Word>(base: T, exponent: T, modulus: T) -> T {
+    check_modulus(modulus, "mod_exp_ct_ladder");
+
+    if modulus & T::one() == T::one() {
+        let ctx = Montgomery::new(modulus);
+        ctx.from_montgomery(ladder(&ConstantTime(ctx), ctx.to_montgomery(base), exponent))
+    } else {
+        ladder(&Plain { modulus }, base % modulus, exponent)
+    }
+}
+
+fn check_modulus<T: Word>(modulus: T, name: &str) {
+    let err = if modulus.is_zero() {
+        ModExpError::ZeroModulus
+    } else if modulus == T::one() {
+        ModExpError::ModulusOne
+    } else {
+        return;
+    };
+    panic!("{}: {}", name, err);
+}
+
+/// Montgomery multiplication with a masked final subtraction
+struct ConstantTime<T>(Montgomery<T>);
+
+impl<T: Word> Reducer<T> for ConstantTime<T> {
+    #[inline]
+    fn one(&self) -> T {
+        self.0.to_montgomery(T::one())
+    }
+
+    #[inline]
+    fn mul(&self, a: T, b: T) -> T {
+        self.0.mul_ct(a, b)
+    }
+}
+
+/// All ones if bit `i` of `exponent` is set, zero otherwise
+#[inline]
+fn bit_mask<T: Word>(exponent: T, i: u32) -> T {
+    T::zero().wrapping_sub((exponent >> i as usize) & T::one())
+}
+
+fn multiply_always<T: Word, R: Reducer<T>>(reducer: &R, base: T, exponent: T) -> T {
+    let mut result = reducer.one();
+    for i in (0..T::BITS).rev() {
+        result = reducer.mul(result, result);
+        let product = reducer.mul(result, base);
+        let mask = bit_mask(exponent, i);
+        result = (product & mask) | (result & !mask);
+    }
+    result
+}
+
+fn ladder<T: Word, R: Reducer<T>>(reducer: &R, base: T, exponent: T) -> T {
+    let mut r0 = reducer.one();
+    let mut r1 = base;
+    for i in (0..T::BITS).rev() {
+        // With the bit set, swap so the squaring lands on x^(k+1) and the
+        // product in the other slot, then swap back.
+        let mask = bit_mask(exponent, i);
+        let swap = (r0 ^ r1) & mask;
+        r0 = r0 ^ swap;
+        r1 = r1 ^ swap;
+
+        r1 = reducer.mul(r0, r1);
+        r0 = reducer.mul(r0, r0);
+
+        let swap = (r0 ^ r1) & mask;
+        r0 = r0 ^ swap;
+        r1 = r1 ^ swap;
+    }
+    r0
+}
+
+#[cfg(test)] mod tests {
+    use super::{mod_exp_ct, mod_exp_ct_ladder};
+    use mod_exp;
+
+    #[test]
+    fn test_matches_mod_exp() {
+        for modulus in 2..=255u8 {
+            for base in (0..=255u8).step_by(7) {
+                for exponent in (0..=255u8).step_by(5) {
+                    let expected = mod_exp(base, exponent, modulus);
+                    assert_eq!(mod_exp_ct(base, exponent, modulus), expected);
+                    assert_eq!(mod_exp_ct_ladder(base, exponent, modulus), expected);
+                }
+            }
+        }
+
+        for &modulus in &[u128::MAX - 158, 1 << 100] {
+            let base = 0x1234_5678_9abc_def0_u128;
+            for &exponent in &[0, 1, u128::MAX, 1 << 127] {
+                let expected = mod_exp(base, exponent, modulus);
+                assert_eq!(mod_exp_ct(base, exponent, modulus), expected);
+                assert_eq!(mod_exp_ct_ladder(base, exponent, modulus), expected);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "mod_exp_ct: modulus is one")]
+    fn test_modulus_one_panics() {
+        mod_exp_ct(3u32, 4, 1);
+    }
+}
@@ -0,0 +1,388 @@
+//! Discrete logarithms: finding `x` with `g^x == h (mod p)`
+//!
+//! Two generic solvers, both running in about `sqrt(n)` multiplications for a
+//! group of order `n`:
+//!
+//! * [`bsgs`], baby-step giant-step, is deterministic and returns the smallest
+//!   logarithm, but keeps a table of `sqrt(n)` powers of `g`.
+//!   [`bsgs_bounded`] caps the table, trading memory for proportionally more
+//!   giant steps.
+//! * [`pollard_rho`] needs constant memory, but is randomized: it finds a
+//!   logarithm with overwhelming probability rather than certainty.
+//!
+//! Both take the order of `g`, or any multiple of it, when it is known;
+//! without one, `p - 1` is used, which is a multiple of the order of every `g`
+//! when `p` is prime.
+//!
+//! When the factorization of the order is known, [`pohlig_hellman`] splits
+//! the problem into one per prime factor `q`, each costing about `sqrt(q)`,
+//! so a group whose order has only small factors offers no security at all.
+//!
+//! # Examples
+//!
+//! ```
+//! use mod_exp::{dlog, mod_exp};
+//!
+//! let (g, p) = (5u64, 1000000007);
+//! let h = mod_exp(g, 123456789, p);
+//! assert_eq!(dlog::bsgs(g, h, p, None), Some(123456789));
+//! ```
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use num::traits::checked_pow;
+
+use crt::crt;
+use error::ModExpError;
+use gcd::{add_mod, gcd, mod_inverse, sub_mod};
+use reduce::Reducer;
+use residue::ModContext;
+use word::{isqrt, Word};
+use {mod_exp, random_below};
+
+/// Walks Pollard's rho tries before giving up
+const RHO_ATTEMPTS: u64 = 32;
+
+/// Most candidate logarithms a rho collision may leave to check
+const RHO_MAX_CANDIDATES: u32 = 1 << 16;
+
+/// Finds the smallest `x` in `[0, order)` with `g^x == h (mod p)` by
+/// baby-step giant-step
+///
+/// Stores `ceil(sqrt(order))` powers of `g` in a hash table, then takes up to
+/// as many giant steps of `g^-ceil(sqrt(order))` from `h`. Returns `None` if
+/// there is no such `x`.
+///
+/// # Panics
+///
+/// Panics if `p` is zero or one, or if `g` is not invertible modulo `p`
+pub fn bsgs<T: Word + Hash>(g: T, h: T, p: T, order: Option<T>) -> Option<T> {
+    match checked_bsgs(g, h, p, order, usize::MAX) {
+        Ok(x) => x,
+        Err(e) => panic!("bsgs: {}", e),
+    }
+}
+
+/// Like [`bsgs`], with a table of at most `max_entries` powers of `g`
+///
+/// With `m = min(max_entries, ceil(sqrt(order)))` entries the search takes up
+/// to `order / m` giant steps, so halving the memory doubles the worst-case
+/// time. The result is the same as `bsgs`'s.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::{dlog, mod_exp};
+///
+/// let (g, p) = (3u32, 65537);
+/// let h = mod_exp(g, 40000, p);
+/// assert_eq!(dlog::bsgs_bounded(g, h, p, None, 16), Some(40000));
+/// ```
+///
+/// # Panics
+///
+/// Panics if `p` is zero or one, or if `g` is not invertible modulo `p`
+pub fn bsgs_bounded<T: Word + Hash>(g: T, h: T, p: T, order: Option<T>, max_entries: usize) -> Option<T> {
+    match checked_bsgs(g, h, p, order, max_entries) {
+        Ok(x) => x,
+        Err(e) => panic!("bsgs_bounded: {}", e),
+    }
+}
+
+fn checked_bsgs<T: Word + Hash>(g: T, h: T, p: T, order: Option<T>, max_entries: usize) -> Result<Option<T>, ModExpError> {
+    let ctx = ModContext::checked_new(p)?;
+    let g_inverse = match mod_inverse(g, p) {
+        Some(inverse) => inverse,
+        None => return Err(ModExpError::NotInvertible),
+    };
+    let n = order.unwrap_or(p - T::one());
+    if n.is_zero() {
+        return Ok(None);
+    }
+
+    let root = isqrt(n);
+    let mut m = if root * root == n { root } else { root + T::one() };
+    if let Some(max) = T::from(max_entries.max(1)) {
+        m = m.min(max);
+    }
+
+    // Baby steps: g^j for j < m, keeping the smallest j for each value
+    let g_repr = ctx.enter(g);
+    let mut table = HashMap::with_capacity(m.to_usize().unwrap_or(0));
+    let mut power = ctx.one();
+    let mut j = T::zero();
+    while j < m {
+        table.entry(power).or_insert(j);
+        power = ctx.mul(power, g_repr);
+        j = j + T::one();
+    }
+
+    // Giant steps: h * g^(-m * i) for i = 0, 1, ...
+    let giant = ctx.enter(mod_exp(g_inverse, m, p));
+    let mut gamma = ctx.enter(h);
+    let mut start = T::zero();
+    loop {
+        if let Some(&j) = table.get(&gamma) {
+            let x = start + j;
+            return Ok(if x < n { Some(x) } else { None });
+        }
+        if n - start <= m {
+            return Ok(None);
+        }
+        start = start + m;
+        gamma = ctx.mul(gamma, giant);
+    }
+}
+
+/// Finds an `x` in `[0, order)` with `g^x == h (mod p)` with Pollard's rho
+///
+/// Walks the group with the usual three-way partition (multiplying by `g`,
+/// by `h`, or squaring), tracking every element as `g^a * h^b`, and solves
+/// for `x` once Floyd's cycle detection finds two walks meeting. The
+/// exponents are tracked modulo `order`, so it must be a multiple of the order
+/// of `g`; without one, `p` must be prime. Candidates are checked before being
+/// returned, so a result is always correct.
+///
+/// Returns `None` if there is no such `x`, or, with negligible probability,
+/// if all of its random walks fail. The result is not necessarily the
+/// smallest logarithm.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::{dlog, mod_exp};
+///
+/// let (g, p) = (2u64, 1000003);
+/// let x = dlog::pollard_rho(g, 123456, p, None).unwrap();
+/// assert_eq!(mod_exp(g, x, p), 123456);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `p` is zero or one, or if `g` is not invertible modulo `p`
+pub fn pollard_rho<T: Word>(g: T, h: T, p: T, order: Option<T>) -> Option<T> {
+    let ctx = match ModContext::checked_new(p) {
+        Ok(ctx) => ctx,
+        Err(e) => panic!("pollard_rho: {}", e),
+    };
+    if mod_inverse(g, p).is_none() {
+        panic!("pollard_rho: {}", ModExpError::NotInvertible);
+    }
+    let n = order.unwrap_or(p - T::one());
+    let h = h % p;
+    if h == T::one() {
+        return Some(T::zero());
+    }
+    if n <= T::one() {
+        return None;
+    }
+
+    let three = T::from(3).unwrap();
+    let (g_repr, h_repr) = (ctx.enter(g), ctx.enter(h));
+    let step = |(x, a, b): (T, T, T)| match (x % three).to_u8() {
+        Some(0) => (ctx.mul(x, h_repr), a, add_mod(b, T::one(), n)),
+        Some(1) => (ctx.mul(x, x), add_mod(a, a, n), add_mod(b, b, n)),
+        _ => (ctx.mul(x, g_repr), add_mod(a, T::one(), n), b),
+    };
+
+    let mut state = 0;
+    for _ in 0..RHO_ATTEMPTS {
+        let a = random_below(n, &mut state);
+        let b = random_below(n, &mut state);
+        let start = (ctx.enter(mod_exp(g, a, p).mul_mod(mod_exp(h, b, p), p)), a, b);
+
+        let (mut tortoise, mut hare) = (step(start), step(step(start)));
+        while tortoise.0 != hare.0 {
+            tortoise = step(tortoise);
+            hare = step(step(hare));
+        }
+
+        // g^a1 h^b1 == g^a2 h^b2, so (b1 - b2) x == a2 - a1 (mod n)
+        let (_, a1, b1) = tortoise;
+        let (_, a2, b2) = hare;
+        let (r, s) = (sub_mod(b1, b2, n), sub_mod(a2, a1, n));
+        if let Some(x) = solve_candidates(r, s, n, |x| mod_exp(g, x, p) == h) {
+            return Some(x);
+        }
+    }
+    None
+}
+
+/// Finds `x` with `g^x == h (mod p)` by the Pohlig–Hellman method, given the
+/// factorization of the order of `g` as `(prime, exponent)` pairs
+///
+/// For each `q^e` dividing the order `n`, the logarithm modulo `q^e` is found
+/// one base-`q` digit at a time, each digit being a logarithm in the subgroup
+/// of order `q`, solved by [`bsgs`]. The results are combined with the Chinese
+/// remainder theorem into the smallest `x` in `[0, n)`. Every step is a
+/// [`mod_exp`] or a multiplication, and the cost is dominated
+/// by `sqrt` of the largest prime factor.
+///
+/// The factors may also describe any multiple of the order, such as `p - 1`
+/// for a prime `p`. Returns `None` if there is no logarithm.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::{dlog, mod_exp};
+///
+/// // p - 1 = 2 * 3^2 * 5 * 7^2 * 11 * 13 * 17 * 19 * 23 * 29 * 31 * 37 has no
+/// // factor large enough to protect a logarithm
+/// let p = 155835500831011u64;
+/// let factors = [(2, 1), (3, 2), (5, 1), (7, 2), (11, 1), (13, 1), (17, 1), (19, 1),
+///                (23, 1), (29, 1), (31, 1), (37, 1)];
+/// let secret = 3141592653589;
+/// let h = mod_exp(5, secret, p);
+/// assert_eq!(dlog::pohlig_hellman(5, h, p, &factors).map(|x| mod_exp(5, x, p)), Some(h));
+/// ```
+///
+/// # Panics
+///
+/// Panics if `p` is zero or one, if `g` is not invertible modulo `p`, or if
+/// the order described by the factors doesn't fit in `T`
+pub fn pohlig_hellman<T: Word + Hash>(g: T, h: T, p: T, order_factors: &[(T, u32)]) -> Option<T> {
+    if let Err(e) = ModContext::checked_new(p) {
+        panic!("pohlig_hellman: {}", e);
+    }
+    let g_inverse = match mod_inverse(g, p) {
+        Some(inverse) => inverse,
+        None => panic!("pohlig_hellman: {}", ModExpError::NotInvertible),
+    };
+
+    let n = order_factors.iter().fold(T::one(), |n, &(q, e)| {
+        match checked_pow(q, e as usize).and_then(|power| n.checked_mul(&power)) {
+            Some(n) => n,
+            None => panic!("pohlig_hellman: {}", ModExpError::Overflow),
+        }
+    });
+
+    let mut congruences = Vec::with_capacity(order_factors.len());
+    for &(q, e) in order_factors {
+        // Can't overflow, as it divides n
+        let power = q.pow(e);
+        // Move into the subgroup of order dividing q^e, and find its exact
+        // order q^f in case the factors describe a multiple of the order
+        let cofactor = n / power;
+        let (g_i, h_i) = (mod_exp(g, cofactor, p), mod_exp(h, cofactor, p));
+        let g_i_inverse = mod_exp(g_inverse, cofactor, p);
+        let (mut f, mut order_i) = (e, power);
+        while f > 0 && mod_exp(g_i, order_i / q, p) == T::one() {
+            f -= 1;
+            order_i = order_i / q;
+        }
+        // gamma generates the subgroup of order q
+        let gamma = mod_exp(g_i, order_i / q, p);
+
+        // x_i = d_0 + d_1 q + ... + d_(f-1) q^(f-1), one digit at a time
+        let mut x_i = T::zero();
+        let mut q_k = T::one();
+        for _ in 0..f {
+            let remaining = mod_exp(g_i_inverse, x_i, p).mul_mod(h_i, p);
+            let target = mod_exp(remaining, order_i / q / q_k, p);
+            let digit = bsgs(gamma, target, p, Some(q))?;
+            x_i = x_i + digit * q_k;
+            q_k = q_k * q;
+        }
+        congruences.push((x_i, order_i));
+    }
+
+    let (x, _) = crt(&congruences)?;
+    if mod_exp(g, x, p) == h % p { Some(x) } else { None }
+}
+
+/// The smallest solution of `r * x == s (mod n)` that passes `check`, unless
+/// there are too many solutions to try
+fn solve_candidates<T: Word, F: Fn(T) -> bool>(r: T, s: T, n: T, check: F) -> Option<T> {
+    if r.is_zero() {
+        return None;
+    }
+    let d = gcd(r, n);
+    if !(s % d).is_zero() || d > T::from(RHO_MAX_CANDIDATES).unwrap_or_else(T::max_value) {
+        return None;
+    }
+    let step = n / d;
+    let inverse = mod_inverse(r / d, step).unwrap();
+    let mut x = (s / d).mul_mod(inverse, step);
+    let mut k = T::zero();
+    while k < d {
+        if check(x) {
+            return Some(x);
+        }
+        x = x + step;
+        k = k + T::one();
+    }
+    None
+}
+
+#[cfg(test)] mod tests {
+    use super::{bsgs, bsgs_bounded, pohlig_hellman, pollard_rho};
+    use mod_exp;
+
+    #[test]
+    fn test_bsgs_matches_brute_force() {
+        for &p in &[2u32, 11, 12, 97, 100, 243, 257] {
+            for g in (1..p).filter(|&g| ::gcd::gcd(g, p) == 1) {
+                let mut logs = vec![None; p as usize];
+                for x in (0..p - 1).rev() {
+                    logs[mod_exp(g, x, p) as usize] = Some(x);
+                }
+                for h in 0..p {
+                    let expected = logs[h as usize];
+                    assert_eq!(bsgs(g, h, p, None), expected, "log_{}({}) mod {}", g, h, p);
+                    assert_eq!(bsgs_bounded(g, h, p, None, 3), expected);
+                }
+            }
+        }
+
+        let p = 0xffff_ffff_0000_0001u64;
+        let g = 7;
+        // 7 generates the group, so restricting to a subgroup of order 2^16
+        // means using g^((p-1)/2^16)
+        let sub = mod_exp(g, (p - 1) >> 16, p);
+        assert_eq!(bsgs(sub, mod_exp(sub, 54321, p), p, Some(1 << 16)), Some(54321));
+        assert_eq!(bsgs(sub, g, p, Some(1 << 16)), None);
+    }
+
+    #[test]
+    fn test_pollard_rho() {
+        for &p in &[1000003u64, 999983, 65537] {
+            for &(g, x) in &[(2u64, 1u64), (3, 77777), (5, 400000), (10, 65535)] {
+                let h = mod_exp(g, x, p);
+                let found = pollard_rho(g, h, p, None).unwrap();
+                assert_eq!(mod_exp(g, found, p), h);
+            }
+        }
+
+        // 4 has order 11 modulo 23, and 5 is not a power of it
+        assert_eq!(pollard_rho(4u32, 5, 23, Some(11)), None);
+        assert_eq!(pollard_rho(4u32, 3, 23, Some(11)).map(|x| mod_exp(4, x, 23)), Some(3));
+
+        let p = 0xffff_ffff_0000_0001u64;
+        let sub = mod_exp(7, (p - 1) >> 20, p);
+        let h = mod_exp(sub, 987654, p);
+        assert_eq!(pollard_rho(sub, h, p, Some(1 << 20)), Some(987654));
+    }
+
+    #[test]
+    fn test_pohlig_hellman() {
+        // 2^16 + 1 is prime, so its group has order 2^16
+        for &(g, x) in &[(3u32, 0u32), (3, 1), (3, 40000), (9, 12345), (2, 31)] {
+            let h = mod_exp(g, x, 65537);
+            assert_eq!(pohlig_hellman(g, h, 65537, &[(2, 16)]), bsgs(g, h, 65537, None));
+        }
+
+        // p - 1 = 2^32 * 3 * 5 * 17 * 257 * 65537
+        let p = 0xffff_ffff_0000_0001u64;
+        let factors = [(2, 32), (3, 1), (5, 1), (17, 1), (257, 1), (65537, 1)];
+        for &x in &[1u64, 0xdead_beef_cafe, p - 2] {
+            let h = mod_exp(7, x, p);
+            assert_eq!(pohlig_hellman(7, h, p, &factors), Some(x));
+        }
+        // 49 generates a subgroup of index 2, which 7 is not in
+        assert_eq!(pohlig_hellman(49, 7, p, &factors), None);
+
+        // Composite modulus: 2 has order lcm(3, 6, 10) = 30 modulo 7 * 9 * 11
+        let m = 7 * 9 * 11;
+        assert_eq!(pohlig_hellman(2u32, mod_exp(2, 25, m), m, &[(2, 1), (3, 1), (5, 1)]), Some(25));
+    }
+}
@@ -0,0 +1,46 @@
+use std::error::Error;
+use std::fmt;
+
+/// The ways a modular exponentiation can fail
+///
+/// Returned by [`checked_mod_exp`](crate::checked_mod_exp). `mod_exp` panics
+/// with the `Display` text of these values instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModExpError {
+    /// The modulus was zero
+    ZeroModulus,
+    /// The modulus was one
+    ModulusOne,
+    /// The modulus was negative
+    NegativeModulus,
+    /// The modulus was even where an odd one is required
+    EvenModulus,
+    /// The base has no inverse modulo the modulus, so it can't be raised to a
+    /// negative power
+    NotInvertible,
+    /// A result or intermediate value would not fit in the integer type
+    Overflow,
+    /// Two residues from contexts with different moduli were combined
+    ContextMismatch,
+    /// A factorization listed one, a repeated prime, or factors that are not
+    /// coprime
+    InvalidFactorization,
+}
+
+impl fmt::Display for ModExpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match *self {
+            ModExpError::ZeroModulus => "modulus is zero",
+            ModExpError::ModulusOne => "modulus is one",
+            ModExpError::NegativeModulus => "modulus is negative",
+            ModExpError::EvenModulus => "modulus is even",
+            ModExpError::NotInvertible => "base is not invertible modulo the modulus",
+            ModExpError::Overflow => "value overflows the integer type",
+            ModExpError::ContextMismatch => "residues belong to different contexts",
+            ModExpError::InvalidFactorization => "factors are not distinct primes",
+        };
+        f.write_str(msg)
+    }
+}
+
+impl Error for ModExpError {}
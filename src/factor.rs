@@ -0,0 +1,463 @@
+use std::iter;
+#[cfg(feature = "bigint")]
+use num::{BigUint, Integer, One, Zero};
+use num::traits::checked_pow;
+
+use bpsw::is_probable_prime;
+#[cfg(feature = "bigint")]
+use bpsw::is_probable_prime_big;
+use gcd::{add_mod, gcd, sub_mod};
+use montgomery::Montgomery;
+use word::Word;
+#[cfg(feature = "bigint")]
+use splitmix64;
+use word::iroot;
+use random_below;
+
+/// Divisors below this are found by trial division, the rest by Pollard's rho
+const TRIAL_BOUND: u32 = 1 << 10;
+
+/// Steps of Brent's walk whose differences are multiplied together before
+/// taking a gcd
+const BRENT_BATCH: usize = 128;
+
+/// Steps of Pollard's rho a part gets before it is handed to ECM
+const RHO_STEPS: usize = 1 << 16;
+
+/// Stage 1 bound of the first ECM curves
+const ECM_B1: u32 = 1 << 10;
+
+/// Largest stage 1 bound ECM goes up to
+const ECM_MAX_B1: u32 = 1 << 20;
+
+/// Curves tried with each stage 1 bound before it is doubled
+const ECM_CURVES: u32 = 8;
+
+/// Factors `n` into primes
+///
+/// Returns `(prime, exponent)` pairs sorted by prime, and none at all for
+/// `n == 1`. Divisors below 1024 are found by trial division. Whatever is
+/// left is split by Pollard's rho with Brent's cycle detection, multiplying
+/// in a [`Montgomery`] context, until every part passes the
+/// [`bpsw`](crate::bpsw) test. Rho takes around `sqrt(q)` steps to find a
+/// prime factor `q`, so a part it hasn't split within `2^16` steps goes on to
+/// stage 1 of the elliptic curve method (ECM), with a bound that grows until
+/// a factor turns up. Neither method can split a power of a single prime, so
+/// perfect powers are taken apart with integer roots before either runs.
+/// Every `u64` factors quickly, and a `u128` with two prime factors of 64
+/// bits each in a couple of seconds. There is no ECM stage 2.
+///
+/// The primality test is exact below `2^64`. Larger parts that pass it are
+/// taken to be prime, though none that is actually composite is known.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::factor;
+///
+/// assert_eq!(factor(360u32), vec![(2, 3), (3, 2), (5, 1)]);
+/// assert_eq!(factor(4294967291u64 * 4294967279), vec![(4294967279, 1), (4294967291, 1)]);
+/// assert_eq!(factor(1u8), vec![]);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `n` is zero
+pub fn factor<T: Word>(n: T) -> Vec<(T, u32)> {
+    if n.is_zero() {
+        panic!("factor: n is zero");
+    }
+
+    let two = T::one() + T::one();
+    let bound = T::from(TRIAL_BOUND);
+    let mut primes = Vec::new();
+    let mut n = n;
+    let mut d = two;
+    while d <= n / d && bound.is_none_or(|b| d < b) {
+        while (n % d).is_zero() {
+            n = n / d;
+            primes.push(d);
+        }
+        d = if d == two { d + T::one() } else { d + two };
+    }
+
+    // Every part left is odd, as Montgomery arithmetic needs
+    let mut parts = if n.is_one() { vec![] } else { vec![n] };
+    let mut state = 0;
+    while let Some(part) = parts.pop() {
+        if d > part / d || is_probable_prime(part) {
+            primes.push(part);
+        } else if let Some((root, k)) = perfect_power(part) {
+            parts.extend(iter::repeat_n(root, k as usize));
+        } else {
+            let divisor = match pollard_brent(part, RHO_STEPS, &mut state) {
+                Some(divisor) => divisor,
+                None => ecm(part, &mut state),
+            };
+            parts.push(divisor);
+            parts.push(part / divisor);
+        }
+    }
+
+    primes.sort();
+    group(primes)
+}
+
+/// Factors `n` into primes, with the trial division and Pollard's rho of
+/// [`factor`]
+///
+/// Returns `(prime, exponent)` pairs sorted by prime. There is no ECM stage
+/// here, so the cost grows with the square root of the second largest prime
+/// factor, and this is only practical when at most one of them is much over
+/// `2^64`.
+///
+/// # Examples
+///
+/// ```
+/// extern crate mod_exp;
+/// extern crate num;
+///
+/// use num::BigUint;
+///
+/// # fn main() {
+/// // The sixth Fermat number, 2^64 + 1
+/// let f6 = (BigUint::from(1u32) << 64) + BigUint::from(1u32);
+/// assert_eq!(mod_exp::factor_big(&f6), vec![(BigUint::from(274177u32), 1), (BigUint::from(67280421310721u64), 1)]);
+/// # }
+/// ```
+///
+/// # Panics
+///
+/// Panics if `n` is zero
+#[cfg(feature = "bigint")]
+pub fn factor_big(n: &BigUint) -> Vec<(BigUint, u32)> {
+    if n.is_zero() {
+        panic!("factor_big: n is zero");
+    }
+
+    let bound = BigUint::from(TRIAL_BOUND);
+    let mut primes = Vec::new();
+    let mut n = n.clone();
+    let mut d = BigUint::from(2u32);
+    while &d * &d <= n && d < bound {
+        while n.is_multiple_of(&d) {
+            n = &n / &d;
+            primes.push(d.clone());
+        }
+        d = if d == BigUint::from(2u32) { d + 1u32 } else { d + 2u32 };
+    }
+
+    let mut parts = if n.is_one() { vec![] } else { vec![n] };
+    let mut state = 0;
+    while let Some(part) = parts.pop() {
+        if &d * &d > part || is_probable_prime_big(&part) {
+            primes.push(part);
+        } else {
+            let divisor = pollard_brent_big(&part, &mut state);
+            parts.push(&part / &divisor);
+            parts.push(divisor);
+        }
+    }
+
+    primes.sort();
+    group(primes)
+}
+
+/// Finds a proper divisor of an odd composite `n` with Pollard's rho, using
+/// Brent's cycle detection and batched gcds, or gives up with `None` after
+/// about `max_steps` steps
+///
+/// Walks `x -> x^2 + c` from a random start, retrying with another random `c`
+/// whenever a walk closes its cycle modulo every factor at once. The walk
+/// stays in Montgomery form, which only scales each difference by a unit and
+/// so leaves the gcds unchanged.
+fn pollard_brent<T: Word>(n: T, max_steps: usize, state: &mut u64) -> Option<T> {
+    let ctx = Montgomery::new(n);
+    let mut steps = 0;
+    loop {
+        let c = random_below(n, state);
+        let f = |x: T| add_mod(ctx.mul(x, x), c, n);
+
+        let mut y = random_below(n, state);
+        let (mut x, mut saved) = (y, y);
+        let (mut product, mut g) = (T::one(), T::one());
+        let mut r = 1;
+        while g.is_one() {
+            x = y;
+            for _ in 0..r {
+                y = f(y);
+            }
+            let mut k = 0;
+            while k < r && g.is_one() {
+                saved = y;
+                for _ in 0..BRENT_BATCH.min(r - k) {
+                    y = f(y);
+                    product = ctx.mul(product, sub_mod(x, y, n));
+                }
+                g = gcd(product, n);
+                k += BRENT_BATCH;
+            }
+            steps += 2 * r;
+            if g.is_one() && steps >= max_steps {
+                return None;
+            }
+            r *= 2;
+        }
+
+        if g == n {
+            // The batch overshot the factor, so retrace it one step at a time
+            loop {
+                saved = f(saved);
+                g = gcd(sub_mod(x, saved, n), n);
+                if !g.is_one() {
+                    break;
+                }
+            }
+        }
+        if g != n {
+            return Some(g);
+        }
+    }
+}
+
+/// Finds a proper divisor of an odd composite `n` with stage 1 of Lenstra's
+/// elliptic curve method
+///
+/// Each curve is a random Montgomery curve from Suyama's parametrization,
+/// whose order modulo a prime factor `q` is a multiple of 12 and otherwise
+/// about as likely to be smooth as a random number near `q`. Multiplying a
+/// point by every prime power up to a bound `B1` takes it to infinity modulo
+/// `q` when that order is `B1`-smooth, and `q` then divides its `Z`
+/// coordinate. Only `X` and `Z` are tracked, with Montgomery's ladder, all in
+/// Montgomery form. `B1` starts small and doubles every few curves, so small
+/// factors come out first and any factor eventually does.
+fn ecm<T: Word>(n: T, state: &mut u64) -> T {
+    let ctx = Montgomery::new(n);
+    let constant = |c: u32| ctx.to_montgomery(T::from(c).unwrap() % n);
+    let (three, four, five, sixteen) = (constant(3), constant(4), constant(5), constant(16));
+    let add = |a: T, b: T| add_mod(a, b, n);
+    let sub = |a: T, b: T| sub_mod(a, b, n);
+    let mul = |a: T, b: T| ctx.mul(a, b);
+    let cube = |a: T| mul(a, mul(a, a));
+
+    let mut b1 = ECM_B1;
+    let mut primes = primes_up_to(b1);
+    let mut curves = 0;
+    loop {
+        if curves == ECM_CURVES && b1 < ECM_MAX_B1 {
+            b1 *= 2;
+            primes = primes_up_to(b1);
+            curves = 0;
+        }
+        curves += 1;
+
+        // u = sigma^2 - 5 and v = 4 sigma give the starting point (u^3 : v^3)
+        // on the curve with (A + 2) / 4 = a24 / c24
+        let sigma = ctx.to_montgomery(random_below(n, state));
+        let u = sub(mul(sigma, sigma), five);
+        let v = mul(four, sigma);
+        let a24 = mul(cube(sub(v, u)), add(mul(three, u), v));
+        let c24 = mul(sixteen, mul(cube(u), v));
+        let g = gcd(c24, n);
+        if !g.is_one() {
+            if g != n {
+                return g;
+            }
+            continue;
+        }
+
+        let double = |(x, z): (T, T)| {
+            let (s, d) = (add(x, z), sub(x, z));
+            let (s, d) = (mul(s, s), mul(d, d));
+            let t = sub(s, d);
+            let cd = mul(c24, d);
+            (mul(cd, s), mul(t, add(cd, mul(a24, t))))
+        };
+        // P + Q from P, Q and P - Q
+        let differential_add = |(xp, zp): (T, T), (xq, zq): (T, T), (xd, zd): (T, T)| {
+            let t0 = mul(sub(xp, zp), add(xq, zq));
+            let t1 = mul(add(xp, zp), sub(xq, zq));
+            let (s, d) = (add(t0, t1), sub(t0, t1));
+            (mul(zd, mul(s, s)), mul(xd, mul(d, d)))
+        };
+        let ladder = |point: (T, T), k: u32| {
+            let (mut r0, mut r1) = (point, double(point));
+            for i in (0..31 - k.leading_zeros()).rev() {
+                if (k >> i) & 1 == 1 {
+                    r0 = differential_add(r0, r1, point);
+                    r1 = double(r1);
+                } else {
+                    r1 = differential_add(r0, r1, point);
+                    r0 = double(r0);
+                }
+            }
+            r0
+        };
+
+        // Stage 1, checking the gcd once at the end, or after every prime
+        // when that found all the factors of n at once
+        let stage_1 = |each_prime: bool| {
+            let mut point = (cube(u), cube(v));
+            for &q in &primes {
+                let mut power = q;
+                while power <= b1 / q {
+                    power *= q;
+                }
+                point = ladder(point, power);
+                if each_prime && !gcd(point.1, n).is_one() {
+                    break;
+                }
+            }
+            gcd(point.1, n)
+        };
+        let mut g = stage_1(false);
+        if g == n {
+            g = stage_1(true);
+        }
+        if !g.is_one() && g != n {
+            return g;
+        }
+    }
+}
+
+/// `(root, k)` with `root^k == n` for a prime `k`, if `n` is a perfect power
+///
+/// Rho and ECM both need two distinct prime factors to split `n`, so powers of
+/// a single prime have to be taken apart first. Every part reaching this has
+/// no factor below [`TRIAL_BOUND`], which bounds `k` by `BITS / 10`.
+fn perfect_power<T: Word>(n: T) -> Option<(T, u32)> {
+    [2, 3, 5, 7, 11].iter()
+        .filter(|&&k| 10 * k <= T::BITS)
+        .map(|&k| (iroot(n, k), k))
+        .find(|&(root, k)| checked_pow(root, k as usize) == Some(n))
+}
+
+/// The primes up to `bound`, by the sieve of Eratosthenes
+fn primes_up_to(bound: u32) -> Vec<u32> {
+    let mut composite = vec![false; bound as usize + 1];
+    let mut primes = Vec::new();
+    for i in 2..=bound {
+        if !composite[i as usize] {
+            primes.push(i);
+            for j in (i as usize * i as usize..=bound as usize).step_by(i as usize) {
+                composite[j] = true;
+            }
+        }
+    }
+    primes
+}
+
+/// [`pollard_brent`] for `BigUint`, in plain arithmetic
+#[cfg(feature = "bigint")]
+fn pollard_brent_big(n: &BigUint, state: &mut u64) -> BigUint {
+    let abs_diff = |a: &BigUint, b: &BigUint| if a >= b { a - b } else { b - a };
+    loop {
+        let c = BigUint::from(splitmix64(state)) % n;
+        let f = |x: &BigUint| (x * x + &c) % n;
+
+        let mut y = BigUint::from(splitmix64(state)) % n;
+        let (mut x, mut saved) = (y.clone(), y.clone());
+        let (mut product, mut g) = (BigUint::one(), BigUint::one());
+        let mut r = 1;
+        while g.is_one() {
+            x = y.clone();
+            for _ in 0..r {
+                y = f(&y);
+            }
+            let mut k = 0;
+            while k < r && g.is_one() {
+                saved = y.clone();
+                for _ in 0..BRENT_BATCH.min(r - k) {
+                    y = f(&y);
+                    product = product * abs_diff(&x, &y) % n;
+                }
+                g = product.gcd(n);
+                k += BRENT_BATCH;
+            }
+            r *= 2;
+        }
+
+        if &g == n {
+            loop {
+                saved = f(&saved);
+                g = abs_diff(&x, &saved).gcd(n);
+                if !g.is_one() {
+                    break;
+                }
+            }
+        }
+        if &g != n {
+            return g;
+        }
+    }
+}
+
+/// Collapses a sorted list of primes into `(prime, exponent)` pairs
+fn group<T: PartialEq>(primes: Vec<T>) -> Vec<(T, u32)> {
+    let mut factors: Vec<(T, u32)> = Vec::new();
+    for p in primes {
+        match factors.last_mut() {
+            Some(&mut (ref q, ref mut e)) if *q == p => *e += 1,
+            _ => factors.push((p, 1)),
+        }
+    }
+    factors
+}
+
+#[cfg(test)] mod tests {
+    use super::{ecm, factor};
+    use is_prime;
+
+    #[test]
+    fn test_factor() {
+        for n in 1..20000u32 {
+            let factors = factor(n);
+            assert!(factors.windows(2).all(|w| w[0].0 < w[1].0), "{}: {:?}", n, factors);
+            assert!(factors.iter().all(|&(p, e)| is_prime(p) && e > 0), "{}: {:?}", n, factors);
+            assert_eq!(factors.iter().map(|&(p, e)| p.pow(e)).product::<u32>(), n);
+            assert_eq!(factor(n as u64), factors.iter().map(|&(p, e)| (p as u64, e)).collect::<Vec<_>>());
+        }
+
+        assert_eq!(factor(u64::MAX), vec![(3, 1), (5, 1), (17, 1), (257, 1), (641, 1), (65537, 1), (6700417, 1)]);
+        assert_eq!(factor(1u128 << 127), vec![(2, 127)]);
+        assert_eq!(factor(u8::MAX), vec![(3, 1), (5, 1), (17, 1)]);
+        // A square and a cube of primes above the trial division bound
+        assert_eq!(factor(1009u64 * 1009 * 4099 * 4099 * 4099), vec![(1009, 2), (4099, 3)]);
+        // Three primes of around 40 bits
+        let (p, q, r) = (1099511627791u128, 1099511627689, 1099511627803);
+        assert_eq!(factor(p * q * r), vec![(q, 1), (p, 1), (r, 1)]);
+    }
+
+    #[test]
+    fn test_ecm() {
+        let mut state = 1;
+        for &n in &[4294967291u128 * 4294967279, 1099511627791 * 1099511627689, 1000003 * 1000003 * 1000033] {
+            let d = ecm(n, &mut state);
+            assert!(d > 1 && d < n && n % d == 0, "{}: {}", n, d);
+        }
+        // Two primes of 48 bits, far more than rho's step budget
+        let (p, q) = (281474976710597u128, 281474976710591);
+        assert_eq!(factor(p * q), vec![(q, 1), (p, 1)]);
+
+        // Powers of one large prime, which neither rho nor ECM can split
+        assert_eq!(factor(2147483647u64 * 2147483647), vec![(2147483647, 2)]);
+        assert_eq!(factor(18446744073709551557u128 * 18446744073709551557), vec![(18446744073709551557, 2)]);
+        assert_eq!(factor(1000003u128.pow(5)), vec![(1000003, 5)]);
+        assert_eq!(factor(1000003u128.pow(6) * 3), vec![(3, 1), (1000003, 6)]);
+        assert_eq!(factor(4294967291u128 * 4294967291 * 1099511627791), vec![(4294967291, 2), (1099511627791, 1)]);
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_factor_big_matches_factor() {
+        use num::BigUint;
+        use super::factor_big;
+
+        let mut state = 1;
+        for _ in 0..200 {
+            let n = ::splitmix64(&mut state) >> (state % 40);
+            let expected: Vec<(BigUint, u32)> = factor(n).into_iter().map(|(p, e)| (BigUint::from(p), e)).collect();
+            assert_eq!(factor_big(&BigUint::from(n)), expected, "{}", n);
+        }
+    }
+}
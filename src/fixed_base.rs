@@ -0,0 +1,135 @@
+use error::ModExpError;
+use reduce::Reducer;
+use residue::ModContext;
+use strategy::{exponent_bits, pow_with, window_value, Strategy};
+use word::Word;
+
+/// Largest window size a table will be built with
+const MAX_WINDOW: u32 = 16;
+
+/// Precomputed powers of a fixed base, for raising it to many exponents
+///
+/// With a window of `k` bits the table holds `base^(d * 2^(k*j))` for every
+/// digit `d` in `1..2^k` and every window `j` of an exponent up to `max_bits`
+/// long. Evaluating [`pow`](FixedBase::pow) is then one multiplication per
+/// non-zero window and no squarings at all, against roughly `1.5 * max_bits`
+/// multiplications for [`mod_exp`](crate::mod_exp).
+///
+/// The table costs `ceil(max_bits / k) * (2^k - 1)` values: larger windows
+/// mean fewer multiplications per exponent but exponentially more memory and
+/// set-up time. Windows are clamped to `1..=16`.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::{mod_exp, FixedBase};
+///
+/// let p = 0xffff_ffff_0000_0001u64;
+/// let g = FixedBase::new(7, p, 64, 4);
+/// for &secret in &[1u64, 0xdead_beef, u64::MAX - 1] {
+///     assert_eq!(g.pow(secret), mod_exp(7, secret, p));
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FixedBase<T> {
+    ctx: ModContext<T>,
+    base: T,
+    window: usize,
+    max_bits: usize,
+    /// `window_count` rows of `2^window - 1` entries, in the context's
+    /// representation
+    table: Vec<T>,
+}
+
+impl<T: Word> FixedBase<T> {
+    /// Builds the table for `base` modulo `modulus`, covering exponents of up
+    /// to `max_bits` bits with windows of `window` bits
+    ///
+    /// # Panics
+    ///
+    /// Panics if the modulus is zero or one
+    pub fn new(base: T, modulus: T, max_bits: u32, window: u32) -> FixedBase<T> {
+        match FixedBase::checked_new(base, modulus, max_bits, window) {
+            Ok(table) => table,
+            Err(e) => panic!("FixedBase::new: {}", e),
+        }
+    }
+
+    /// Builds the table, or reports why the modulus can't be used
+    pub fn checked_new(base: T, modulus: T, max_bits: u32, window: u32) -> Result<FixedBase<T>, ModExpError> {
+        let ctx = ModContext::checked_new(modulus)?;
+        let window = window.clamp(1, MAX_WINDOW) as usize;
+        let max_bits = max_bits as usize;
+        let row = (1 << window) - 1;
+        let windows = max_bits.div_ceil(window);
+
+        let mut table = Vec::with_capacity(windows * row);
+        // power = base^(2^(window * j)) at the start of row j
+        let mut power = ctx.enter(base);
+        for _ in 0..windows {
+            let mut entry = power;
+            table.push(entry);
+            for _ in 1..row {
+                entry = ctx.mul(entry, power);
+                table.push(entry);
+            }
+            // entry is power^(2^window - 1); one more factor moves to the next row
+            power = ctx.mul(entry, power);
+        }
+
+        Ok(FixedBase { ctx, base, window, max_bits, table })
+    }
+
+    /// The modulus
+    pub fn modulus(&self) -> T {
+        self.ctx.modulus()
+    }
+
+    /// Computes `base^exponent mod n`
+    ///
+    /// Exponents longer than the `max_bits` the table was built for are still
+    /// computed correctly, with an ordinary sliding window exponentiation.
+    pub fn pow(&self, exponent: T) -> T {
+        let bits = exponent_bits(exponent);
+        if bits.len() > self.max_bits {
+            let base = self.ctx.enter(self.base);
+            return self.ctx.leave(pow_with(&self.ctx, Strategy::Auto, base, exponent));
+        }
+
+        let row = (1 << self.window) - 1;
+        let mut result = self.ctx.one();
+        for (j, lo) in (0..bits.len()).step_by(self.window).enumerate() {
+            let d = window_value(&bits, lo, lo + self.window);
+            if d != 0 {
+                result = self.ctx.mul(result, self.table[j * row + d - 1]);
+            }
+        }
+        self.ctx.leave(result)
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::FixedBase;
+    use {mod_exp, ModExpError};
+
+    #[test]
+    fn test_matches_mod_exp() {
+        for &modulus in &[1000000007u64, 1 << 50, u64::MAX] {
+            for window in 0..8 {
+                let g = FixedBase::new(3, modulus, 48, window);
+                for &e in &[0u64, 1, 2, 0xabcd, (1 << 48) - 1, 1 << 48, u64::MAX] {
+                    assert_eq!(g.pow(e), mod_exp(3, e, modulus));
+                }
+            }
+        }
+
+        let p = u128::MAX - 158;
+        let g = FixedBase::new(5, p, 128, 6);
+        assert_eq!(g.pow(p - 1), 1);
+    }
+
+    #[test]
+    fn test_checked_new() {
+        assert_eq!(FixedBase::checked_new(2u32, 1, 32, 4).map(|g| g.modulus()), Err(ModExpError::ModulusOne));
+    }
+}
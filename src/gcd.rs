@@ -0,0 +1,337 @@
+use std::mem;
+use std::ops::Shr;
+use num::traits::{Num, One, Signed, Zero};
+
+use error::ModExpError;
+use wide::WideningMulMod;
+use {check_modulus, rem_euclid};
+
+/// Computes the greatest common divisor of `a` and `b` along with Bézout
+/// coefficients
+///
+/// Returns `(g, x, y)` with `a * x + b * y == g` and `g` non-negative. The
+/// coefficients can be negative, so this needs a signed type; see
+/// [`mod_inverse`] for the one thing most callers want them for. Like `abs`,
+/// it overflows when `g` would be `2^(BITS - 1)`, i.e. for
+/// `extended_gcd(MIN, MIN)`, `extended_gcd(MIN, 0)` and `extended_gcd(0, MIN)`.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::extended_gcd;
+///
+/// let (g, x, y) = extended_gcd(240i64, 46);
+/// assert_eq!(g, 2);
+/// assert_eq!(240 * x + 46 * y, 2);
+/// ```
+pub fn extended_gcd<T>(a: T, b: T) -> (T, T, T) where T: Num + Signed + Copy {
+    let (mut r0, mut r1) = (a, b);
+    let (mut x0, mut x1) = (T::one(), T::zero());
+    let (mut y0, mut y1) = (T::zero(), T::one());
+
+    while !r1.is_zero() {
+        // MIN / -1 overflows, so flip the signs of a -1 remainder first
+        if r1 == -T::one() {
+            r1 = T::one();
+            x1 = -x1;
+            y1 = -y1;
+        }
+        let q = r0 / r1;
+        let r = r0 - q * r1;
+        r0 = r1;
+        r1 = r;
+        // The last coefficients would be +-b/g and +-a/g, which can overflow
+        // and aren't needed
+        if r.is_zero() {
+            x0 = x1;
+            y0 = y1;
+            break;
+        }
+        let x = x0 - q * x1;
+        x0 = x1;
+        x1 = x;
+        let y = y0 - q * y1;
+        y0 = y1;
+        y1 = y;
+    }
+
+    if r0.is_negative() {
+        (-r0, -x0, -y0)
+    } else {
+        (r0, x0, y0)
+    }
+}
+
+#[allow(non_snake_case)]
+/// Computes the inverse of `a` modulo `modulus`
+///
+/// Returns the `x` in `[0, modulus)` with `a * x == 1 (mod modulus)`, or
+/// `None` if `a` and `modulus` share a factor or `modulus` is not positive.
+/// Works for unsigned types too: the extended Euclidean algorithm only tracks
+/// the coefficient of `a`, and keeps it reduced modulo `modulus`.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::mod_inverse;
+///
+/// assert_eq!(mod_inverse(3u32, 11), Some(4));
+/// assert_eq!(mod_inverse(-3i32, 11), Some(7));
+/// assert_eq!(mod_inverse(6u32, 9), None);
+/// ```
+pub fn mod_inverse<T>(a: T, modulus: T) -> Option<T> where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod {
+    let ONE: T = One::one();
+    let ZERO: T = Zero::zero();
+
+    if modulus <= ZERO {
+        return None;
+    }
+
+    let mut a = a % modulus;
+    if a < ZERO {
+        a = a + modulus;
+    }
+
+    // Invariant: s_i * a == r_i (mod modulus)
+    let (mut r0, mut r1) = (modulus, a);
+    let (mut s0, mut s1) = (ZERO, ONE % modulus);
+
+    while r1 != ZERO {
+        let q = r0 / r1;
+        let r = r0 - q * r1;
+        r0 = r1;
+        r1 = r;
+        let s = sub_mod(s0, q.mul_mod(s1, modulus), modulus);
+        s0 = s1;
+        s1 = s;
+    }
+
+    if r0 == ONE { Some(s0) } else { None }
+}
+
+#[allow(non_snake_case)]
+/// Computes the greatest common divisor of `a` and `b` by Stein's binary
+/// algorithm
+///
+/// Halves by shifting and tests parity with `% 2`, which compiles to a bit
+/// test for the primitive types; beyond that it only subtracts and doubles,
+/// with no general division, which pays off where division is slow. The
+/// result is non-negative, and `gcd(0, 0)` is `0`. For signed types it
+/// overflows, like `abs`, when it would be `2^(BITS - 1)`, i.e. for
+/// `binary_gcd(MIN, MIN)` and `binary_gcd(MIN, 0)`.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::binary_gcd;
+///
+/// assert_eq!(binary_gcd(240u32, 46), 2);
+/// assert_eq!(binary_gcd(-12i64, 18), 6);
+/// assert_eq!(binary_gcd(0u8, 0), 0);
+/// ```
+pub fn binary_gcd<T>(a: T, b: T) -> T where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod {
+    let ONE: T = One::one();
+    let ZERO: T = Zero::zero();
+    let TWO = ONE + ONE;
+    let abs = |x: T| if x < ZERO { ZERO - x } else { x };
+
+    if a.is_zero() {
+        return abs(b);
+    }
+    if b.is_zero() {
+        return abs(a);
+    }
+
+    // Shifting halves even values exactly, negative ones included, and once
+    // they are odd they can be negated without overflowing
+    let (mut a, mut b) = (a, b);
+    let mut common_twos = 0;
+    while (a % TWO).is_zero() && (b % TWO).is_zero() {
+        a = a >> ONE;
+        b = b >> ONE;
+        common_twos += 1;
+    }
+    while (a % TWO).is_zero() {
+        a = a >> ONE;
+    }
+    a = abs(a);
+    loop {
+        while (b % TWO).is_zero() {
+            b = b >> ONE;
+        }
+        // Both are odd here, so their difference is even
+        b = abs(b);
+        if a > b {
+            mem::swap(&mut a, &mut b);
+        }
+        b = (b - a) >> ONE;
+        if b.is_zero() {
+            break;
+        }
+    }
+    (0..common_twos).fold(a, |g, _| g * TWO)
+}
+
+#[allow(non_snake_case)]
+/// Replaces every value in `values` by its inverse modulo `modulus`
+///
+/// Montgomery's trick: the running products of the values are inverted with a
+/// single [`mod_inverse`], and the individual inverses peeled off that one
+/// with two multiplications each, so `n` values take one inversion and
+/// `3(n - 1)` multiplications. The inverses are in `[0, modulus)`.
+///
+/// Fails with [`ModExpError::NotInvertible`] if any value shares a factor with
+/// `modulus`, and otherwise in the same cases as
+/// [`checked_mod_exp`](crate::checked_mod_exp) for the modulus. On failure
+/// `values` is left as it was.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::{batch_inverse, ModExpError};
+///
+/// let mut values = [2u32, 3, 4, 5];
+/// batch_inverse(&mut values, 11).unwrap();
+/// assert_eq!(values, [6, 4, 3, 9]);
+///
+/// let mut values = [2i64, -3, 4];
+/// assert_eq!(batch_inverse(&mut values, 12), Err(ModExpError::NotInvertible));
+/// assert_eq!(values, [2, -3, 4]);
+/// ```
+pub fn batch_inverse<T>(values: &mut [T], modulus: T) -> Result<(), ModExpError> where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod {
+    check_modulus(modulus)?;
+    let (first, rest) = match values.split_first_mut() {
+        Some(split) => split,
+        None => return Ok(()),
+    };
+
+    // before[i] is the product of the values ahead of rest[i]
+    let mut before = Vec::with_capacity(rest.len());
+    let mut product = rem_euclid(*first, modulus);
+    for &value in rest.iter() {
+        before.push(product);
+        product = product.mul_mod(rem_euclid(value, modulus), modulus);
+    }
+    let mut inverse = match mod_inverse(product, modulus) {
+        Some(inverse) => inverse,
+        None => return Err(ModExpError::NotInvertible),
+    };
+
+    // Going backwards, inverse is that of the product up to and including
+    // rest[i], so multiplying by before[i] leaves the inverse of rest[i]
+    for (value, &before) in rest.iter_mut().zip(before.iter()).rev() {
+        let reduced = rem_euclid(*value, modulus);
+        *value = inverse.mul_mod(before, modulus);
+        inverse = inverse.mul_mod(reduced, modulus);
+    }
+    *first = inverse;
+    Ok(())
+}
+
+/// The greatest common divisor of two non-negative values, by Euclid's
+/// algorithm
+pub(crate) fn gcd<T>(a: T, b: T) -> T where T: Num + Copy {
+    let (mut a, mut b) = (a, b);
+    while !b.is_zero() {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    a
+}
+
+/// `(a + b) mod m` for `a` and `b` in `[0, m)`, without overflowing
+pub(crate) fn add_mod<T>(a: T, b: T, m: T) -> T where T: Num + PartialOrd + Copy {
+    if a >= m - b { a - (m - b) } else { a + b }
+}
+
+/// `(a - b) mod m` for `a` and `b` in `[0, m)`, without leaving that range
+pub(crate) fn sub_mod<T>(a: T, b: T, m: T) -> T where T: Num + PartialOrd + Copy {
+    if a >= b { a - b } else { a + (m - b) }
+}
+
+#[cfg(test)] mod tests {
+    use super::{batch_inverse, binary_gcd, extended_gcd, gcd, mod_inverse};
+    use ModExpError;
+
+    #[test]
+    fn test_extended_gcd() {
+        for a in i8::MIN..=i8::MAX {
+            for b in i8::MIN..=i8::MAX {
+                let expected = gcd(a as i32, b as i32).abs();
+                if expected > i8::MAX as i32 {
+                    continue;
+                }
+                let (g, x, y) = extended_gcd(a, b);
+                assert_eq!(g as i32, expected, "gcd({}, {})", a, b);
+                assert_eq!(a as i32 * x as i32 + b as i32 * y as i32, expected, "gcd({}, {})", a, b);
+            }
+        }
+
+        for &(a, b, g) in &[(i32::MIN, -1, 1), (-1, i32::MIN, 1), (i32::MIN, 1, 1), (i32::MIN, i32::MAX, 1), (i32::MIN, 6, 2), (i32::MIN, i32::MIN + 2, 2)] {
+            let (actual, x, y) = extended_gcd(a, b);
+            assert_eq!(actual, g);
+            assert_eq!(a as i64 * x as i64 + b as i64 * y as i64, g as i64, "gcd({}, {})", a, b);
+        }
+    }
+
+    #[test]
+    fn test_mod_inverse() {
+        for m in 1u8..=255 {
+            for a in 0u8..=255 {
+                let brute = (0..m).find(|&x| (a as u32 * x as u32) % m as u32 == 1 % m as u32);
+                assert_eq!(mod_inverse(a, m), brute);
+            }
+        }
+
+        let p = u128::MAX - 158;
+        let inv = mod_inverse(p - 2, p).unwrap();
+        assert_eq!(::WideningMulMod::mul_mod(inv, p - 2, p), 1);
+        assert_eq!(mod_inverse(i64::MIN, 7), Some(6));
+        assert_eq!(mod_inverse(3i32, -7), None);
+    }
+
+    #[test]
+    fn test_binary_gcd() {
+        for a in -128i16..=127 {
+            for b in -128i16..=127 {
+                let expected = gcd(a, b).abs();
+                assert_eq!(binary_gcd(a, b), expected, "gcd({}, {})", a, b);
+                if expected < 128 {
+                    assert_eq!(binary_gcd(a as i8, b as i8), expected as i8);
+                }
+            }
+        }
+        let k = (1u128 << 100) + 7;
+        assert_eq!(binary_gcd(6 * k, 10 * k), 2 * k);
+        assert_eq!(binary_gcd(1u64 << 40, 3 << 50), 1 << 40);
+    }
+
+    #[test]
+    fn test_batch_inverse() {
+        for m in 2u16..300 {
+            let units: Vec<u16> = (0..m).filter(|&a| gcd(a, m) == 1).collect();
+            let mut values = units.clone();
+            batch_inverse(&mut values, m).unwrap();
+            for (&a, &inverse) in units.iter().zip(values.iter()) {
+                assert_eq!(Some(inverse), mod_inverse(a, m));
+            }
+
+            // One value sharing a factor with m spoils the whole batch
+            if let Some(bad) = (2..m).find(|&a| gcd(a, m) != 1) {
+                let mut values = units.clone();
+                values.insert(values.len() / 2, bad);
+                let before = values.clone();
+                assert_eq!(batch_inverse(&mut values, m), Err(ModExpError::NotInvertible));
+                assert_eq!(values, before);
+            }
+        }
+
+        let mut values: [i32; 0] = [];
+        assert_eq!(batch_inverse(&mut values, 7), Ok(()));
+        let mut values = [i64::MIN, -1, 5];
+        batch_inverse(&mut values, 7).unwrap();
+        assert_eq!(values, [6, 6, 3]);
+        assert_eq!(batch_inverse(&mut [1u8], 1), Err(ModExpError::ModulusOne));
+    }
+}
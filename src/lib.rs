@@ -6,10 +6,61 @@
 
 extern crate num;
 
+mod barrett;
+#[cfg(feature = "bigint")]
+pub mod bigint;
+pub mod bpsw;
+mod crt;
+mod ct;
+pub mod dlog;
+mod error;
+mod factor;
+mod fixed_base;
+mod gcd;
+mod modint;
+mod montgomery;
+mod multi;
+mod order;
+mod prime;
+mod reduce;
+mod residue;
+mod sqrt;
+mod strategy;
+mod symbol;
+mod totient;
+mod wide;
+mod word;
+
 use std::ops::{Shr};
-use num::traits::{Num, One, Zero, Bounded};
+use num::traits::{Num, One, Zero};
+
+use reduce::Plain;
+use strategy::pow_with;
+
+pub use barrett::Barrett;
+pub use crt::{checked_crt, checked_mod_exp_crt, crt, mod_exp_crt};
+pub use ct::{mod_exp_ct, mod_exp_ct_ladder};
+pub use error::ModExpError;
+pub use factor::factor;
+#[cfg(feature = "bigint")]
+pub use factor::factor_big;
+pub use fixed_base::FixedBase;
+pub use gcd::{batch_inverse, binary_gcd, extended_gcd, mod_inverse};
+pub use modint::ModInt;
+pub use montgomery::Montgomery;
+pub use multi::{checked_multi_mod_exp, multi_mod_exp};
+pub use order::{is_primitive_root, multiplicative_order, primitive_root};
+pub use prime::is_prime;
+pub use residue::{ModContext, Residue};
+pub use sqrt::{sqrt_mod, sqrt_mod_cipolla, sqrt_mod_composite, sqrt_mod_prime_power};
+pub use strategy::Strategy;
+pub use symbol::{jacobi, kronecker, legendre};
+pub use totient::{carmichael, carmichael_from_factors, checked_mod_exp_reduced, checked_tower_mod, mod_exp_reduced, totient, totient_from_factors, tower_mod};
+#[cfg(feature = "bigint")]
+pub use totient::{checked_mod_exp_reduced_big, mod_exp_reduced_big};
+pub use wide::WideningMulMod;
+pub use word::Word;
 
-#[allow(non_snake_case)]
 /// Performs the exponentiation
 ///
 /// All parameters are generic, provided they implement the following traits:
@@ -18,9 +69,15 @@ use num::traits::{Num, One, Zero, Bounded};
 /// * PartialOrd
 /// * Shr<T, Output=T>
 /// * Copy
-/// * Bounded
+/// * WideningMulMod
+///
+/// You can find the `Num` trait in the [num](https://crates.io/crate/num) crate.
+/// [`WideningMulMod`] is implemented for all of the primitive integer types,
+/// and lets any modulus representable in `T` be used without overflowing.
 ///
-/// You can find the `Num` and `Bounded` traits in the [num](https://crates.io/crate/num) crate.
+/// Odd moduli are handled by a [`Montgomery`] context built for the call;
+/// even moduli use the square-and-multiply loop with a `%` at every step. For
+/// repeated exponentiation with a fixed even modulus, see [`Barrett`].
 ///
 /// # Examples
 ///
@@ -28,42 +85,192 @@ use num::traits::{Num, One, Zero, Bounded};
 /// use mod_exp::mod_exp;
 ///
 /// assert_eq!(mod_exp(5, 3, 13), 8);
+/// assert_eq!(mod_exp(5, -3, 13), 5);
+/// assert_eq!(mod_exp(-2, 3, 7), 6);
+/// ```
+///
+/// The result is always the least non-negative residue, in `[0, modulus)`,
+/// including for negative bases of signed types. A negative exponent raises
+/// the inverse of `base` (see [`mod_inverse`]) to the corresponding positive
+/// power.
+///
+/// # Panics
+///
+/// Panics whenever [`checked_mod_exp`] would return an error: when the modulus
+/// is zero, one or negative, or when the exponent is negative and `base` has
+/// no inverse modulo `modulus`
+pub fn mod_exp<T>(base: T, exponent: T, modulus: T) -> T where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod {
+    match checked_mod_exp(base, exponent, modulus) {
+        Ok(result) => result,
+        Err(e) => panic!("mod_exp: {}", e),
+    }
+}
+
+/// Performs the exponentiation, reporting invalid input instead of panicking
+///
+/// Takes the same parameters as [`mod_exp`], but returns a [`ModExpError`]
+/// describing the problem whenever `mod_exp` would panic.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::{checked_mod_exp, ModExpError};
+///
+/// assert_eq!(checked_mod_exp(5, 3, 13), Ok(8));
+/// assert_eq!(checked_mod_exp(5, 3, 0), Err(ModExpError::ZeroModulus));
+/// assert_eq!(checked_mod_exp(4, -3, 14), Err(ModExpError::NotInvertible));
+/// ```
+pub fn checked_mod_exp<T>(base: T, exponent: T, modulus: T) -> Result<T, ModExpError> where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod {
+    checked_mod_exp_with(Strategy::Binary, base, exponent, modulus)
+}
+
+/// Performs the exponentiation, scanning the exponent with the given [`Strategy`]
+///
+/// The result is the same as [`mod_exp`]'s for every strategy; only the number
+/// of multiplications differs.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::{mod_exp_with, Strategy};
+///
+/// assert_eq!(mod_exp_with(Strategy::SlidingWindow(4), 5u64, 1 << 60, 1000), 625);
+/// assert_eq!(mod_exp_with(Strategy::Auto, 4, 13, 497), 445);
 /// ```
 ///
 /// # Panics
 ///
-/// The function does an `assert!` to verify that the data type of `base` is
-/// large enough that the result won't overflow during the computation
-pub fn mod_exp<T>(base: T, exponent: T, modulus: T) -> T where T: Num + PartialOrd + Shr<T, Output=T> + Copy + Bounded {
+/// Panics in the same cases as [`mod_exp`]
+pub fn mod_exp_with<T>(strategy: Strategy, base: T, exponent: T, modulus: T) -> T where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod {
+    match checked_mod_exp_with(strategy, base, exponent, modulus) {
+        Ok(result) => result,
+        Err(e) => panic!("mod_exp: {}", e),
+    }
+}
+
+#[allow(non_snake_case)]
+/// Performs the exponentiation with the given [`Strategy`], reporting invalid
+/// input instead of panicking
+pub fn checked_mod_exp_with<T>(strategy: Strategy, base: T, exponent: T, modulus: T) -> Result<T, ModExpError> where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod {
+    let ZERO: T = Zero::zero();
+
+    check_modulus(modulus)?;
+    if exponent < ZERO {
+        let (inverse, rest) = invert_negative_exponent(base, exponent, modulus)?;
+        let rest = checked_mod_exp_with(strategy, inverse, rest, modulus)?;
+        return Ok(rest.mul_mod(inverse, modulus));
+    }
+
+    let base = rem_euclid(base, modulus);
+    if let Some(result) = base.montgomery_pow(exponent, modulus, strategy) {
+        return Ok(result);
+    }
+
+    Ok(pow_with(&Plain { modulus }, strategy, base, exponent))
+}
+
+/// Rejects the moduli [`checked_mod_exp`] can't work with: zero, negative ones
+/// and one
+pub(crate) fn check_modulus<T>(modulus: T) -> Result<(), ModExpError> where T: Num + PartialOrd + Copy {
+    if modulus.is_zero() {
+        Err(ModExpError::ZeroModulus)
+    } else if modulus < T::zero() {
+        Err(ModExpError::NegativeModulus)
+    } else if modulus.is_one() {
+        Err(ModExpError::ModulusOne)
+    } else {
+        Ok(())
+    }
+}
+
+#[allow(non_snake_case)]
+/// Turns a negative `exponent` into a non-negative one: returns the inverse of
+/// `base` and `-exponent - 1`, with `base^exponent == inverse^rest * inverse`
+///
+/// `-exponent` itself overflows for the most negative value, so one factor of
+/// the inverse is left for the caller to multiply back in.
+pub(crate) fn invert_negative_exponent<T>(base: T, exponent: T, modulus: T) -> Result<(T, T), ModExpError> where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod {
     let ONE: T = One::one();
-    let TWO: T = ONE + ONE;
     let ZERO: T = Zero::zero();
-    let MAX: T = Bounded::max_value();
 
-    assert!((modulus - ONE)  < (MAX / (modulus - ONE)));
+    match mod_inverse(base, modulus) {
+        Some(inverse) => Ok((inverse, ZERO - (exponent + ONE))),
+        None => Err(ModExpError::NotInvertible),
+    }
+}
 
-    let mut result = ONE;
-    let mut base = base % modulus;
-    let mut exponent = exponent;
+/// The least non-negative residue of `a` modulo a positive `modulus`
+pub(crate) fn rem_euclid<T>(a: T, modulus: T) -> T where T: Num + PartialOrd + Copy {
+    let r = a % modulus;
+    if r < T::zero() { r + modulus } else { r }
+}
 
-    loop {
-        if exponent <= ZERO {
-            break;
-        }
+/// Steps the SplitMix64 generator and returns its next output
+pub(crate) fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
 
-        if exponent % TWO == ONE {
-            result = (result * base) % modulus;
-        }
+/// A pseudo-random value in `[0, n)`, drawn from [`splitmix64`]
+pub(crate) fn random_below<T: Word>(n: T, state: &mut u64) -> T {
+    let wide = ((splitmix64(state) as u128) << 64) | splitmix64(state) as u128;
+    T::from(wide % n.to_u128().unwrap()).unwrap()
+}
 
-        exponent = exponent >> ONE;
-        base = (base * base) % modulus;
+/// Modular exponentiation as a method, for primitive and big integers alike
+///
+/// Implemented for every primitive integer type by forwarding to
+/// [`checked_mod_exp`], and for `BigUint` and `BigInt` by forwarding to
+/// `bigint::checked_mod_exp` when the `bigint` feature is enabled. Generic
+/// code bounded on this trait accepts either.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::ModExp;
+///
+/// fn fermat_witness<T: ModExp + PartialEq>(a: &T, n_minus_one: &T, n: &T, one: &T) -> bool {
+///     a.mod_exp(n_minus_one, n) != *one
+/// }
+///
+/// assert!(fermat_witness(&2u32, &8, &9, &1));
+/// assert!(!fermat_witness(&2u32, &12, &13, &1));
+/// ```
+pub trait ModExp: Sized {
+    /// Computes `self^exponent mod modulus`, or reports why it can't
+    fn checked_mod_exp(&self, exponent: &Self, modulus: &Self) -> Result<Self, ModExpError>;
+
+    /// Computes `self^exponent mod modulus`
+    ///
+    /// # Panics
+    ///
+    /// Panics whenever [`checked_mod_exp`](ModExp::checked_mod_exp) would
+    /// return an error
+    fn mod_exp(&self, exponent: &Self, modulus: &Self) -> Self {
+        match self.checked_mod_exp(exponent, modulus) {
+            Ok(result) => result,
+            Err(e) => panic!("mod_exp: {}", e),
+        }
     }
+}
 
-    result
+macro_rules! mod_exp_impl {
+    ($($t:ty),*) => {$(
+        impl ModExp for $t {
+            fn checked_mod_exp(&self, exponent: &$t, modulus: &$t) -> Result<$t, ModExpError> {
+                checked_mod_exp(*self, *exponent, *modulus)
+            }
+        }
+    )*}
 }
 
+mod_exp_impl!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
 #[cfg(test)] mod tests {
-    use super::mod_exp;
+    use super::{checked_mod_exp, mod_exp, mod_inverse, ModExpError, WideningMulMod};
     use std::panic;
 
     #[test]
@@ -75,16 +282,103 @@ pub fn mod_exp<T>(base: T, exponent: T, modulus: T) -> T where T: Num + PartialO
     }
 
     #[test]
-    fn test_overflow_lhs() {
+    fn test_large_modulus() {
+        let p = 18446744073709551557u64;
+        assert_eq!(mod_exp(3, p - 1, p), 1);
+        assert_eq!(mod_exp(200u8, 3, 251), 128);
+
+        let p = u128::MAX - 158;
+        assert_eq!(mod_exp(3, p - 1, p), 1);
+        assert_eq!(mod_exp(2, 128, p), 159);
+    }
+
+    #[test]
+    fn test_signed_matches_naive() {
+        for base in -20i16..20 {
+            for modulus in 2i16..12 {
+                let mut expected = 1i16;
+                for _ in 0..5 {
+                    expected = (expected * base).rem_euclid(modulus);
+                }
+                assert_eq!(mod_exp(base, 5, modulus), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_negative_exponent() {
+        for base in 1i64..20 {
+            for exponent in 1i64..20 {
+                let inverse = mod_inverse(base, 23).unwrap();
+                assert_eq!(mod_exp(base, -exponent, 23), mod_exp(inverse, exponent, 23));
+            }
+        }
+        assert_eq!(mod_exp(3i8, i8::MIN, 7), mod_exp(mod_inverse(3, 7).unwrap(), 128i32, 7) as i8);
+    }
+
+    macro_rules! signed_residue_tests {
+        ($($name:ident: $t:ty),*) => {$(
+            #[test]
+            fn $name() {
+                let moduli: [$t; 5] = [2, 7, 10, 97, <$t>::MAX];
+                for &modulus in &moduli {
+                    for &base in &[<$t>::MIN, <$t>::MIN + 1, -100, -7, -2, -1, 0, 3, <$t>::MAX] {
+                        for &exponent in &[0 as $t, 1, 2, 3, 13, -1, -3, <$t>::MIN, <$t>::MAX] {
+                            let expected = naive(base as i128, exponent as i128, modulus as i128);
+                            let result = checked_mod_exp(base, exponent, modulus).map(|r| r as i128);
+                            assert_eq!(result.ok(), expected, "{}^{} mod {}", base, exponent, modulus);
+                        }
+                    }
+                }
+            }
+        )*}
+    }
+
+    signed_residue_tests!(test_residue_i8: i8, test_residue_i16: i16, test_residue_i32: i32,
+                          test_residue_i64: i64, test_residue_i128: i128, test_residue_isize: isize);
+
+    /// Reference square-and-multiply in `i128`, on Euclidean remainders
+    fn naive(base: i128, exponent: i128, modulus: i128) -> Option<i128> {
+        let base = base.rem_euclid(modulus);
+        let base = if exponent < 0 {
+            let inverse = mod_inverse(base, modulus)?;
+            assert_eq!(base.mul_mod(inverse, modulus), 1);
+            inverse
+        } else {
+            base
+        };
+        let mut exponent = exponent.unsigned_abs();
+        let mut result = 1;
+        let mut square = base;
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = result.mul_mod(square, modulus);
+            }
+            square = square.mul_mod(square, modulus);
+            exponent >>= 1;
+        }
+        Some(result)
+    }
+
+    #[test]
+    fn test_zero_modulus_panics() {
         if let Err(ref e) = panic::catch_unwind(|| {
-            let modulus = 254u8;
-            mod_exp(1u8, 1u8, modulus);
+            mod_exp(1u8, 1u8, 0u8);
         }) {
-            if let Some(msg) = e.downcast_ref::<&str>() {
-                assert!(msg.starts_with("assertion failed: "));
+            if let Some(msg) = e.downcast_ref::<String>() {
+                assert!(msg.starts_with("mod_exp: "));
                 return
             }
         }
-        assert!(false, "Assertion didn't fail as it should have");
+        panic!("Assertion didn't fail as it should have");
+    }
+
+    #[test]
+    fn test_checked_mod_exp_errors() {
+        assert_eq!(checked_mod_exp(3i32, 4, 0), Err(ModExpError::ZeroModulus));
+        assert_eq!(checked_mod_exp(3i32, 4, 1), Err(ModExpError::ModulusOne));
+        assert_eq!(checked_mod_exp(3i32, 4, -7), Err(ModExpError::NegativeModulus));
+        assert_eq!(checked_mod_exp(3i32, -4, 9), Err(ModExpError::NotInvertible));
+        assert_eq!(checked_mod_exp(4i64, 13, 497), Ok(445));
     }
 }
@@ -0,0 +1,257 @@
+use std::fmt;
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+use gcd::mod_inverse;
+use mod_exp;
+
+/// A residue modulo the compile-time constant `M`
+///
+/// Values are always kept reduced into `[0, M)`. Multiplication by an odd `M`
+/// goes through Montgomery reduction, with `-M^-1 mod 2^64` and `2^128 mod M`
+/// computed at compile time; an even `M` falls back to a 128 bit `%`.
+/// Division multiplies by the inverse, and panics if there is none.
+///
+/// Using an `M` below two is a compile error:
+///
+/// ```compile_fail
+/// use mod_exp::ModInt;
+///
+/// let zero = ModInt::<1>::default();
+/// ```
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::ModInt;
+///
+/// type Mint = ModInt<998244353>;
+///
+/// let a = Mint::new(3);
+/// let b = Mint::from(998244352u64);
+/// assert_eq!(a + b, Mint::new(2));
+/// assert_eq!(a.pow(998244352), Mint::new(1));
+/// assert_eq!((Mint::new(1) / a) * a, Mint::new(1));
+/// assert_eq!((-a).to_string(), "998244350");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ModInt<const M: u64> {
+    value: u64,
+}
+
+impl<const M: u64> ModInt<M> {
+    /// The modulus
+    pub const MODULUS: u64 = M;
+
+    const VALID: () = assert!(M > 1, "ModInt modulus must be at least 2");
+
+    /// `-M^-1 mod 2^64`, or zero for an even `M`
+    const N_PRIME: u64 = neg_inverse_mod_2_64(M);
+
+    /// `(2^64)^2 mod M`, for converting a Montgomery product back
+    const R2: u64 = if M > 1 {
+        let r1 = (1u128 << 64) % M as u128;
+        (r1 * r1 % M as u128) as u64
+    } else {
+        0
+    };
+
+    /// Reduces `value` modulo `M`
+    pub fn new(value: u64) -> ModInt<M> {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID;
+        ModInt { value: value % M }
+    }
+
+    /// The residue, in `[0, M)`
+    pub fn value(self) -> u64 {
+        self.value
+    }
+
+    /// Raises the residue to `exponent` with [`mod_exp`](crate::mod_exp)
+    pub fn pow(self, exponent: u64) -> ModInt<M> {
+        ModInt { value: mod_exp(self.value, exponent, M) }
+    }
+
+    /// The multiplicative inverse, if the residue is coprime to `M`
+    pub fn inverse(self) -> Option<ModInt<M>> {
+        mod_inverse(self.value, M).map(|value| ModInt { value })
+    }
+
+    /// Montgomery reduction of `x < M * 2^64`
+    #[inline]
+    fn redc(x: u128) -> u64 {
+        let m = (x as u64).wrapping_mul(Self::N_PRIME);
+        let (sum, carry) = x.overflowing_add(m as u128 * M as u128);
+        let t = (sum >> 64) | ((carry as u128) << 64);
+        if t >= M as u128 { (t - M as u128) as u64 } else { t as u64 }
+    }
+}
+
+/// Newton's iteration for `-n^-1 mod 2^64`; zero when `n` is even and has no
+/// inverse
+const fn neg_inverse_mod_2_64(n: u64) -> u64 {
+    if n & 1 == 0 {
+        return 0;
+    }
+    let mut inv = n;
+    let mut i = 0;
+    while i < 6 {
+        inv = inv.wrapping_mul(2u64.wrapping_sub(n.wrapping_mul(inv)));
+        i += 1;
+    }
+    inv.wrapping_neg()
+}
+
+impl<const M: u64> fmt::Display for ModInt<M> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.value, f)
+    }
+}
+
+impl<const M: u64> Default for ModInt<M> {
+    /// Zero, through [`ModInt::new`] so that `M` is checked
+    fn default() -> ModInt<M> {
+        ModInt::new(0)
+    }
+}
+
+impl<const M: u64> From<u64> for ModInt<M> {
+    fn from(value: u64) -> ModInt<M> {
+        ModInt::new(value)
+    }
+}
+
+impl<const M: u64> From<u32> for ModInt<M> {
+    fn from(value: u32) -> ModInt<M> {
+        ModInt::new(value as u64)
+    }
+}
+
+impl<const M: u64> From<i64> for ModInt<M> {
+    /// Takes the least non-negative residue, so `-1` maps to `M - 1`
+    fn from(value: i64) -> ModInt<M> {
+        let r = ModInt::new(value.unsigned_abs());
+        if value < 0 { -r } else { r }
+    }
+}
+
+impl<const M: u64> From<ModInt<M>> for u64 {
+    fn from(m: ModInt<M>) -> u64 {
+        m.value
+    }
+}
+
+impl<const M: u64> Add for ModInt<M> {
+    type Output = ModInt<M>;
+
+    fn add(self, rhs: ModInt<M>) -> ModInt<M> {
+        let (sum, carry) = self.value.overflowing_add(rhs.value);
+        let value = if carry || sum >= M { sum.wrapping_sub(M) } else { sum };
+        ModInt { value }
+    }
+}
+
+impl<const M: u64> Sub for ModInt<M> {
+    type Output = ModInt<M>;
+
+    fn sub(self, rhs: ModInt<M>) -> ModInt<M> {
+        let value = if self.value >= rhs.value {
+            self.value - rhs.value
+        } else {
+            self.value.wrapping_sub(rhs.value).wrapping_add(M)
+        };
+        ModInt { value }
+    }
+}
+
+impl<const M: u64> Mul for ModInt<M> {
+    type Output = ModInt<M>;
+
+    fn mul(self, rhs: ModInt<M>) -> ModInt<M> {
+        let product = self.value as u128 * rhs.value as u128;
+        let value = if M & 1 == 1 {
+            // Each reduction divides by 2^64; multiplying by R2 in between
+            // puts it back.
+            Self::redc(Self::redc(product) as u128 * Self::R2 as u128)
+        } else {
+            (product % M as u128) as u64
+        };
+        ModInt { value }
+    }
+}
+
+impl<const M: u64> Div for ModInt<M> {
+    type Output = ModInt<M>;
+
+    /// # Panics
+    ///
+    /// Panics if `rhs` is not invertible modulo `M`
+    fn div(self, rhs: ModInt<M>) -> ModInt<M> {
+        match rhs.inverse() {
+            Some(inverse) => Mul::mul(self, inverse),
+            None => panic!("ModInt: {} is not invertible modulo {}", rhs, M),
+        }
+    }
+}
+
+impl<const M: u64> Neg for ModInt<M> {
+    type Output = ModInt<M>;
+
+    fn neg(self) -> ModInt<M> {
+        ModInt { value: if self.value == 0 { 0 } else { M - self.value } }
+    }
+}
+
+macro_rules! assign_impl {
+    ($($trait_:ident::$method:ident => $op:ident::$op_method:ident),*) => {$(
+        impl<const M: u64> $trait_ for ModInt<M> {
+            fn $method(&mut self, rhs: ModInt<M>) {
+                *self = $op::$op_method(*self, rhs);
+            }
+        }
+    )*}
+}
+
+assign_impl!(AddAssign::add_assign => Add::add, SubAssign::sub_assign => Sub::sub,
+             MulAssign::mul_assign => Mul::mul, DivAssign::div_assign => Div::div);
+
+#[cfg(test)] mod tests {
+    use super::ModInt;
+
+    #[test]
+    fn test_arithmetic_matches_u128() {
+        fn check<const M: u64>() {
+            let values = [0, 1, 2, M / 2, M - 2, M - 1, 0x1234_5678_9abc_def0 % M];
+            for &a in &values {
+                for &b in &values {
+                    let (x, y) = (ModInt::<M>::new(a), ModInt::<M>::new(b));
+                    let m = M as u128;
+                    assert_eq!((x + y).value() as u128, (a as u128 + b as u128) % m);
+                    assert_eq!((x - y).value() as u128, (a as u128 + m - b as u128) % m);
+                    assert_eq!((x * y).value() as u128, a as u128 * b as u128 % m);
+                    assert_eq!((x + -x).value(), 0);
+                }
+            }
+        }
+
+        check::<998244353>();
+        check::<1000000007>();
+        check::<{ 1 << 40 }>();
+        check::<18446744073709551557>();
+        check::<{ u64::MAX }>();
+        check::<2>();
+    }
+
+    #[test]
+    fn test_division_and_conversions() {
+        type Mint = ModInt<1000000007>;
+        assert_eq!(Mint::default(), Mint::new(0));
+        let mut x = Mint::from(-1i64);
+        assert_eq!(u64::from(x), 1000000006);
+        x /= Mint::from(2u32);
+        x *= Mint::new(2);
+        assert_eq!(x, Mint::from(-1i64));
+        assert_eq!(Mint::new(0).inverse(), None);
+        assert_eq!(ModInt::<12>::new(5).inverse(), Some(ModInt::new(5)));
+    }
+}
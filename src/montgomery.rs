@@ -0,0 +1,191 @@
+use error::ModExpError;
+use reduce::Reducer;
+use strategy::{pow_with, Strategy};
+use word::Word;
+
+/// Precomputed Montgomery reduction context for a fixed odd modulus
+///
+/// Building the context costs a few multiplications and one division; after
+/// that every modular multiplication is done with multiplies, adds and shifts
+/// only. When many exponentiations share a modulus, build the context once and
+/// call [`pow`](Montgomery::pow) on it instead of [`mod_exp`](crate::mod_exp).
+///
+/// With `w` the bit width of `T` and `R = 2^w`, values in Montgomery form are
+/// stored as `x * R mod n`.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::Montgomery;
+///
+/// let ctx = Montgomery::new(497u64);
+/// assert_eq!(ctx.pow(4, 13), 445);
+/// assert_eq!(ctx.pow(5, 3), 125);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Montgomery<T> {
+    modulus: T,
+    /// `-n^-1 mod 2^w`
+    n_prime: T,
+    /// `R mod n`, which is one in Montgomery form
+    r1: T,
+    /// `R^2 mod n`
+    r2: T,
+}
+
+impl<T: Word> Montgomery<T> {
+    /// Builds the context for `modulus`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the modulus is even or one
+    pub fn new(modulus: T) -> Montgomery<T> {
+        match Montgomery::checked_new(modulus) {
+            Ok(ctx) => ctx,
+            Err(e) => panic!("Montgomery::new: {}", e),
+        }
+    }
+
+    /// Builds the context for `modulus`, or reports why it can't be used
+    pub fn checked_new(modulus: T) -> Result<Montgomery<T>, ModExpError> {
+        if modulus.is_zero() {
+            return Err(ModExpError::ZeroModulus);
+        }
+        if modulus == T::one() {
+            return Err(ModExpError::ModulusOne);
+        }
+        if modulus & T::one() != T::one() {
+            return Err(ModExpError::EvenModulus);
+        }
+
+        // Newton's iteration doubles the number of correct low bits each
+        // step, starting from 3 since n * n == 1 mod 8 for every odd n.
+        let two = T::one() + T::one();
+        let mut inv = modulus;
+        while modulus.wrapping_mul(inv) != T::one() {
+            inv = inv.wrapping_mul(two.wrapping_sub(modulus.wrapping_mul(inv)));
+        }
+
+        let r1 = T::zero().wrapping_sub(modulus) % modulus;
+        Ok(Montgomery {
+            modulus,
+            n_prime: T::zero().wrapping_sub(inv),
+            r1,
+            r2: r1.mul_mod(r1, modulus),
+        })
+    }
+
+    /// The modulus this context reduces by
+    pub fn modulus(&self) -> T {
+        self.modulus
+    }
+
+    /// Converts `x` into Montgomery form
+    pub fn to_montgomery(&self, x: T) -> T {
+        self.mul(x % self.modulus, self.r2)
+    }
+
+    /// Converts `x` out of Montgomery form
+    pub fn from_montgomery(&self, x: T) -> T {
+        self.redc(x, T::zero())
+    }
+
+    /// Multiplies two values that are both in Montgomery form
+    #[inline]
+    pub fn mul(&self, a: T, b: T) -> T {
+        let (lo, hi) = a.widening_mul(b);
+        self.redc(lo, hi)
+    }
+
+    /// Computes `base^exponent mod n`, taking and returning ordinary integers
+    pub fn pow(&self, base: T, exponent: T) -> T {
+        self.pow_with(Strategy::Binary, base, exponent)
+    }
+
+    /// Like [`pow`](Montgomery::pow), scanning the exponent with `strategy`
+    pub fn pow_with(&self, strategy: Strategy, base: T, exponent: T) -> T {
+        self.from_montgomery(pow_with(self, strategy, self.to_montgomery(base), exponent))
+    }
+
+    /// Multiplies two values in Montgomery form without branching on them
+    #[inline]
+    pub(crate) fn mul_ct(&self, a: T, b: T) -> T {
+        let (lo, hi) = a.widening_mul(b);
+        let (t, overflow) = self.redc_unreduced(lo, hi);
+        // Subtract n when t overflowed or t >= n, choosing with a mask
+        // instead of a branch.
+        let (diff, borrow) = t.overflowing_sub(self.modulus);
+        let keep_diff = overflow | !borrow;
+        let mask = T::zero().wrapping_sub(if keep_diff { T::one() } else { T::zero() });
+        (diff & mask) | (t & !mask)
+    }
+
+    /// Montgomery reduction of `hi * 2^w + lo`, which must be below `n * R`
+    #[inline]
+    fn redc(&self, lo: T, hi: T) -> T {
+        let (t, overflow) = self.redc_unreduced(lo, hi);
+        if overflow || t >= self.modulus {
+            t.wrapping_sub(self.modulus)
+        } else {
+            t
+        }
+    }
+
+    /// `(lo + m * n) / R` before the final subtraction, as a value below `2n`
+    /// and whether it overflowed `T`
+    #[inline]
+    fn redc_unreduced(&self, lo: T, hi: T) -> (T, bool) {
+        let m = lo.wrapping_mul(self.n_prime);
+        let (mlo, mhi) = m.widening_mul(self.modulus);
+        // The low half of lo + m * n is zero by construction; only its carry
+        // survives into the high half.
+        let (_, carry) = lo.overflowing_add(mlo);
+        let (t, o1) = hi.overflowing_add(mhi);
+        let (t, o2) = t.overflowing_add(if carry { T::one() } else { T::zero() });
+        (t, o1 | o2)
+    }
+}
+
+impl<T: Word> Reducer<T> for Montgomery<T> {
+    #[inline]
+    fn one(&self) -> T {
+        self.r1
+    }
+
+    #[inline]
+    fn mul(&self, a: T, b: T) -> T {
+        Montgomery::mul(self, a, b)
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::Montgomery;
+    use ModExpError;
+
+    #[test]
+    fn test_pow_matches_plain_loop() {
+        for &m in &[3u8, 5, 101, 251, 255] {
+            let ctx = Montgomery::new(m);
+            for base in 0..=255u8 {
+                let mut expected = 1u32;
+                for _ in 0..13 {
+                    expected = expected * base as u32 % m as u32;
+                }
+                assert_eq!(ctx.pow(base, 13), expected as u8);
+            }
+        }
+
+        let p = u128::MAX - 158;
+        assert_eq!(Montgomery::new(p).pow(3, p - 1), 1);
+        let p = 18446744073709551557u64;
+        assert_eq!(Montgomery::new(p).pow(p - 2, p - 1), 1);
+    }
+
+    #[test]
+    fn test_checked_new() {
+        assert_eq!(Montgomery::checked_new(0u32), Err(ModExpError::ZeroModulus));
+        assert_eq!(Montgomery::checked_new(1u32), Err(ModExpError::ModulusOne));
+        assert_eq!(Montgomery::checked_new(10u32), Err(ModExpError::EvenModulus));
+        assert!(Montgomery::checked_new(11u32).is_ok());
+    }
+}
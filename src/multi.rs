@@ -0,0 +1,294 @@
+use std::ops::Shr;
+use num::traits::{Num, One, Zero};
+
+use error::ModExpError;
+use montgomery::Montgomery;
+use reduce::{Plain, Reducer};
+use strategy::{auto_window, exponent_bits, window_value};
+use wide::WideningMulMod;
+use word::Word;
+use {check_modulus, invert_negative_exponent, rem_euclid};
+
+/// Largest number of pairs that gets one table of all products of powers
+const JOINT_MAX: usize = 4;
+
+/// Number of pairs from which the bucket method beats interleaving
+const PIPPENGER_THRESHOLD: usize = 32;
+
+/// Computes the product of `base^exponent` over all pairs, modulo `modulus`
+///
+/// The result is the same as multiplying together one [`mod_exp`](crate::mod_exp)
+/// per pair, but the squarings are shared. Up to four pairs use Shamir's
+/// trick, widened to a window of bits, with one table of every product of
+/// powers of the bases, so that each window costs a single multiplication.
+/// More pairs are interleaved with Straus' method, one table per base, and
+/// larger inputs use Pippenger's bucket method. As in `mod_exp`, odd moduli
+/// are multiplied in [`Montgomery`](crate::Montgomery) form, and negative
+/// exponents and bases are handled the same way.
+///
+/// Two `u64` pairs take somewhat less time than two calls to `mod_exp`, whose
+/// right-to-left loop squares and multiplies side by side; two `u128` pairs
+/// take about half.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::{mod_exp, multi_mod_exp};
+///
+/// let (a, x, b, y, m) = (3u64, 1234567, 5, 7654321, 1000000007);
+/// let expected = mod_exp(a, x, m) * mod_exp(b, y, m) % m;
+/// assert_eq!(multi_mod_exp(&[(a, x), (b, y)], m), expected);
+/// ```
+///
+/// # Panics
+///
+/// Panics whenever [`checked_multi_mod_exp`] would return an error
+pub fn multi_mod_exp<T>(pairs: &[(T, T)], modulus: T) -> T where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod {
+    match checked_multi_mod_exp(pairs, modulus) {
+        Ok(result) => result,
+        Err(e) => panic!("multi_mod_exp: {}", e),
+    }
+}
+
+#[allow(non_snake_case)]
+/// Computes the product of `base^exponent` over all pairs, reporting invalid
+/// input instead of panicking
+///
+/// Fails in the same cases as [`checked_mod_exp`](crate::checked_mod_exp), for
+/// any of the pairs.
+pub fn checked_multi_mod_exp<T>(pairs: &[(T, T)], modulus: T) -> Result<T, ModExpError> where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod {
+    let ZERO: T = Zero::zero();
+
+    check_modulus(modulus)?;
+
+    let mut bases = Vec::with_capacity(pairs.len());
+    let mut exponents = Vec::with_capacity(pairs.len());
+    for &(base, exponent) in pairs {
+        if exponent < ZERO {
+            let (inverse, rest) = invert_negative_exponent(base, exponent, modulus)?;
+            bases.push(inverse);
+            exponents.push(rest);
+            bases.push(inverse);
+            exponents.push(One::one());
+        } else {
+            bases.push(rem_euclid(base, modulus));
+            exponents.push(exponent);
+        }
+    }
+
+    if let Some(result) = T::montgomery_multi_pow(&bases, &exponents, modulus) {
+        return Ok(result);
+    }
+    Ok(multi_pow(&Plain { modulus }, &bases, &exponents))
+}
+
+/// [`multi_pow`] in the Montgomery form of `ctx`, taking and returning
+/// ordinary integers
+pub(crate) fn montgomery_multi_pow<T: Word, E>(ctx: &Montgomery<T>, bases: &[T], exponents: &[E]) -> T where E: Num + PartialOrd + Shr<E, Output=E> + Copy {
+    let bases: Vec<T> = bases.iter().map(|&base| ctx.to_montgomery(base)).collect();
+    ctx.from_montgomery(multi_pow(ctx, &bases, exponents))
+}
+
+/// Picks Shamir's trick, Straus' method or Pippenger's by the number of bases
+fn multi_pow<T: Copy, E, R: Reducer<T>>(reducer: &R, bases: &[T], exponents: &[E]) -> T where E: Num + PartialOrd + Shr<E, Output=E> + Copy {
+    if bases.len() <= JOINT_MAX {
+        return shamir(reducer, bases, exponents);
+    }
+    let bits: Vec<Vec<bool>> = exponents.iter().map(|&exponent| exponent_bits(exponent)).collect();
+    if bases.len() < PIPPENGER_THRESHOLD {
+        straus(reducer, bases, &bits)
+    } else {
+        pippenger(reducer, bases, &bits)
+    }
+}
+
+/// Width of the windows for [`shamir`]: the one that minimises the squarings,
+/// the multiplications by nonzero joint digits and the table built up front
+fn joint_window(len: usize, count: usize) -> usize {
+    (1..=4).filter(|k| k * count <= 12).min_by_key(|&k| {
+        let entries = 1usize << (k * count);
+        len + len.div_ceil(k) * (entries - 1) / entries + entries
+    }).unwrap_or(1)
+}
+
+#[allow(non_snake_case)]
+/// Shamir's trick, widened to k-bit windows: one table holds every product
+/// `prod_i bases[i]^d_i` with `d_i < 2^k`, so each window costs a single
+/// multiplication however many bases there are
+fn shamir<T: Copy, E, R: Reducer<T>>(reducer: &R, bases: &[T], exponents: &[E]) -> T where E: Num + PartialOrd + Shr<E, Output=E> + Copy {
+    let ONE: E = One::one();
+    let TWO: E = ONE + ONE;
+    let ZERO: E = Zero::zero();
+
+    let len = exponents.iter().map(|&exponent| {
+        let (mut exponent, mut len) = (exponent, 0);
+        while exponent > ZERO {
+            exponent = exponent >> ONE;
+            len += 1;
+        }
+        len
+    }).max().unwrap_or(0);
+    let k = joint_window(len, bases.len());
+
+    // The digit of base i sits in bits k * i.. of the index
+    let mut table = Vec::with_capacity(1 << (k * bases.len()));
+    table.push(reducer.one());
+    for &base in bases {
+        let size = table.len();
+        for d in 1..(1 << k) {
+            for j in 0..size {
+                let next = reducer.mul(table[(d - 1) * size + j], base);
+                table.push(next);
+            }
+        }
+    }
+
+    // The digit of exponent i in window w sits in bits k * i.. of indices[w]
+    let mut indices = vec![0; len.div_ceil(k)];
+    for (i, &exponent) in exponents.iter().enumerate() {
+        let mut exponent = exponent;
+        for index in indices.iter_mut() {
+            let mut digit = 0;
+            for b in 0..k {
+                digit |= ((exponent % TWO == ONE) as usize) << b;
+                exponent = exponent >> ONE;
+            }
+            *index |= digit << (k * i);
+        }
+    }
+
+    let mut result = reducer.one();
+    for (w, &index) in indices.iter().enumerate().rev() {
+        if w + 1 != indices.len() {
+            for _ in 0..k {
+                result = reducer.mul(result, result);
+            }
+        }
+        if index != 0 {
+            result = reducer.mul(result, table[index]);
+        }
+    }
+    result
+}
+
+/// Interleaved k-ary exponentiation with one table per base and one shared
+/// run of squarings
+fn straus<T: Copy, R: Reducer<T>>(reducer: &R, bases: &[T], bits: &[Vec<bool>]) -> T {
+    let len = bits.iter().map(Vec::len).max().unwrap_or(0);
+    let k = auto_window(len) as usize;
+
+    let tables: Vec<Vec<T>> = bases.iter().map(|&base| {
+        let mut table = vec![reducer.one()];
+        for d in 1..(1 << k) {
+            let next = reducer.mul(table[d - 1], base);
+            table.push(next);
+        }
+        table
+    }).collect();
+
+    let mut result = reducer.one();
+    let windows = len.div_ceil(k);
+    for w in (0..windows).rev() {
+        if w + 1 != windows {
+            for _ in 0..k {
+                result = reducer.mul(result, result);
+            }
+        }
+        for (table, bits) in tables.iter().zip(bits) {
+            let d = window_value(bits, w * k, (w + 1) * k);
+            if d != 0 {
+                result = reducer.mul(result, table[d]);
+            }
+        }
+    }
+    result
+}
+
+/// Pippenger's bucket method: per window, each base is multiplied into the
+/// bucket of its digit, and the buckets are combined with two running products
+fn pippenger<T: Copy, R: Reducer<T>>(reducer: &R, bases: &[T], bits: &[Vec<bool>]) -> T {
+    let len = bits.iter().map(Vec::len).max().unwrap_or(0);
+    let c = (usize::BITS - bases.len().leading_zeros()).clamp(2, 16) as usize;
+
+    let mut result = reducer.one();
+    let windows = len.div_ceil(c);
+    for w in (0..windows).rev() {
+        if w + 1 != windows {
+            for _ in 0..c {
+                result = reducer.mul(result, result);
+            }
+        }
+
+        // buckets[d - 1] collects the bases whose digit in this window is d
+        let mut buckets = vec![reducer.one(); (1 << c) - 1];
+        for (&base, bits) in bases.iter().zip(bits) {
+            let d = window_value(bits, w * c, (w + 1) * c);
+            if d != 0 {
+                buckets[d - 1] = reducer.mul(buckets[d - 1], base);
+            }
+        }
+
+        // prod_d buckets[d]^d, as the product of the suffix products
+        let mut running = reducer.one();
+        let mut total = reducer.one();
+        for &bucket in buckets.iter().rev() {
+            running = reducer.mul(running, bucket);
+            total = reducer.mul(total, running);
+        }
+        result = reducer.mul(result, total);
+    }
+    result
+}
+
+#[cfg(test)] mod tests {
+    use super::{checked_multi_mod_exp, multi_mod_exp, pippenger, shamir, straus};
+    use reduce::Plain;
+    use strategy::exponent_bits;
+    use {mod_exp, ModExpError, WideningMulMod};
+
+    fn product_of_mod_exps(pairs: &[(i64, i64)], modulus: i64) -> i64 {
+        pairs.iter().fold(1, |acc, &(b, e)| acc.mul_mod(mod_exp(b, e, modulus), modulus))
+    }
+
+    #[test]
+    fn test_matches_individual_mod_exps() {
+        let modulus = 1000000007i64;
+        for count in 0..80 {
+            let pairs: Vec<(i64, i64)> = (0..count)
+                .map(|i| (i * 7919 - 300, (i * i * 104729 + 3) % (1 << 40) - if i % 5 == 0 { 1 << 20 } else { 0 }))
+                .collect();
+            assert_eq!(multi_mod_exp(&pairs, modulus), product_of_mod_exps(&pairs, modulus));
+        }
+
+        let pairs = [(3i64, i64::MIN), (5, i64::MAX), (-2, 12345)];
+        assert_eq!(multi_mod_exp(&pairs, (1 << 61) - 1), product_of_mod_exps(&pairs, (1 << 61) - 1));
+        assert_eq!(checked_multi_mod_exp(&[(2i64, 1), (4, -1)], 8), Err(ModExpError::NotInvertible));
+
+        // Odd moduli go through Montgomery form, even ones don't
+        let pairs: Vec<(u128, u128)> = (1..40).map(|i| (i * 0x9e37_79b9_7f4a_7c15, u128::MAX / i)).collect();
+        for &modulus in &[(1u128 << 127) - 1, (1 << 126) + 2, u64::MAX as u128, 1 << 64, 255, 256] {
+            for count in &[2, pairs.len()] {
+                let pairs = &pairs[..*count];
+                let expected = pairs.iter().fold(1, |acc, &(b, e)| acc.mul_mod(mod_exp(b, e, modulus), modulus));
+                assert_eq!(multi_mod_exp(pairs, modulus), expected, "{}", modulus);
+            }
+        }
+        assert_eq!(multi_mod_exp(&[(-3i8, 77), (5, -1)], 127) as i64, product_of_mod_exps(&[(-3, 77), (5, -1)], 127));
+    }
+
+    #[test]
+    fn test_shamir_straus_and_pippenger_agree() {
+        let modulus = 0xffff_ffff_ffff_ffc5u64;
+        let reducer = Plain { modulus };
+        for count in 1..40u64 {
+            let bases: Vec<u64> = (0..count).map(|i| i * 0x9e37_79b9 + 2).collect();
+            let exponents: Vec<u64> = (0..count).map(|i| (u64::MAX / (i + 1)) >> (i % 7)).collect();
+            let bits: Vec<Vec<bool>> = exponents.iter().map(|&exponent| exponent_bits(exponent)).collect();
+            let expected = straus(&reducer, &bases, &bits);
+            assert_eq!(pippenger(&reducer, &bases, &bits), expected);
+            if count <= 6 {
+                assert_eq!(shamir(&reducer, &bases, &exponents), expected);
+            }
+        }
+    }
+}
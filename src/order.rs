@@ -0,0 +1,166 @@
+use std::ops::Shr;
+use num::traits::{Num, NumCast};
+
+use gcd::gcd;
+use totient::{carmichael_from_factors, factor_positive, totient_from_factors};
+use wide::WideningMulMod;
+use {check_modulus, mod_exp, rem_euclid};
+
+/// Computes the multiplicative order of `a` modulo `n`, the smallest `k > 0`
+/// with `a^k == 1 (mod n)`
+///
+/// Returns `None` if `a` is not coprime to `n`, when no such `k` exists. The
+/// order divides [`carmichael(n)`](crate::carmichael), so it is found by
+/// dividing the prime factors out of that for as long as the power stays
+/// one. Both `n` and `lambda(n)` are factored with [`factor`](crate::factor).
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::multiplicative_order;
+///
+/// assert_eq!(multiplicative_order(2, 7), Some(3));
+/// assert_eq!(multiplicative_order(-1i32, 10), Some(2));
+/// assert_eq!(multiplicative_order(4u64, 10), None);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `n` is zero, one or negative
+pub fn multiplicative_order<T>(a: T, n: T) -> Option<T> where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod + NumCast {
+    if let Err(e) = check_modulus(n) {
+        panic!("multiplicative_order: {}", e);
+    }
+    let a = rem_euclid(a, n);
+    if !gcd(a, n).is_one() {
+        return None;
+    }
+
+    let lambda = carmichael_from_factors(&factor_positive(n, "multiplicative_order"));
+    let mut order = lambda;
+    for (q, _) in factor_positive(lambda, "multiplicative_order") {
+        while (order % q).is_zero() && mod_exp(a, order / q, n).is_one() {
+            order = order / q;
+        }
+    }
+    Some(order)
+}
+
+/// Checks whether `g` is a primitive root modulo `n`, i.e. whether its powers
+/// run through every residue coprime to `n`
+///
+/// That is the case when the order of `g` is `phi(n)`, which takes one
+/// [`mod_exp`] per prime factor `q` of `phi(n)` to rule out `g^(phi(n)/q)`
+/// being one. Usually `n` is a prime `p`, where this asks whether `g`
+/// generates the whole multiplicative group; moduli other than 2, 4, `p^k` and
+/// `2p^k` have no primitive roots at all.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::is_primitive_root;
+///
+/// assert!(is_primitive_root(3, 7));
+/// assert!(!is_primitive_root(2, 7));
+/// assert!(is_primitive_root(3u64, 998244353));
+/// ```
+///
+/// # Panics
+///
+/// Panics if `n` is zero, one or negative
+pub fn is_primitive_root<T>(g: T, n: T) -> bool where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod + NumCast {
+    if let Err(e) = check_modulus(n) {
+        panic!("is_primitive_root: {}", e);
+    }
+    match group_order(n, "is_primitive_root") {
+        Some((phi, phi_factors)) => generates(rem_euclid(g, n), n, phi, &phi_factors),
+        None => false,
+    }
+}
+
+/// Finds the smallest positive primitive root modulo `n`
+///
+/// Returns `None` if there is none, which is the case unless `n` is 2, 4, or
+/// `p^k` or `2p^k` for an odd prime `p`. The smallest primitive root is small
+/// in practice, so this tries candidates in turn with the test of
+/// [`is_primitive_root`], having factored `phi(n)` once.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::primitive_root;
+///
+/// assert_eq!(primitive_root(7), Some(3));
+/// assert_eq!(primitive_root(998244353u64), Some(3));
+/// assert_eq!(primitive_root(8), None);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `n` is zero, one or negative
+pub fn primitive_root<T>(n: T) -> Option<T> where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod + NumCast {
+    if let Err(e) = check_modulus(n) {
+        panic!("primitive_root: {}", e);
+    }
+    let (phi, phi_factors) = group_order(n, "primitive_root")?;
+
+    let mut g = T::one();
+    while g < n {
+        if generates(g, n, phi, &phi_factors) {
+            return Some(g);
+        }
+        g = g + T::one();
+    }
+    None
+}
+
+/// `phi(n)` and its factorization, or `None` if the group of units modulo `n`
+/// isn't cyclic, i.e. `lambda(n) != phi(n)`
+fn group_order<T>(n: T, name: &str) -> Option<(T, Vec<(T, u32)>)> where T: Num + PartialOrd + Copy + NumCast {
+    let factors = factor_positive(n, name);
+    let phi = totient_from_factors(&factors);
+    if carmichael_from_factors(&factors) != phi {
+        return None;
+    }
+    Some((phi, factor_positive(phi, name)))
+}
+
+/// Whether `g` in `[0, n)` has order `phi`, given the factorization of `phi`
+fn generates<T>(g: T, n: T, phi: T, phi_factors: &[(T, u32)]) -> bool where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod {
+    gcd(g, n).is_one() && phi_factors.iter().all(|&(q, _)| !mod_exp(g, phi / q, n).is_one())
+}
+
+#[cfg(test)] mod tests {
+    use super::{is_primitive_root, multiplicative_order, primitive_root};
+    use {mod_exp, totient};
+
+    #[test]
+    fn test_matches_brute_force() {
+        for n in 2..300u32 {
+            let mut smallest_root = None;
+            for a in 0..n {
+                let order = (1..=n).find(|&k| mod_exp(a, k, n) == 1);
+                assert_eq!(multiplicative_order(a, n), order, "ord({} mod {})", a, n);
+                assert_eq!(multiplicative_order(a as i32 - n as i32, n as i32), order.map(|k| k as i32));
+
+                let is_root = order == Some(totient(n));
+                assert_eq!(is_primitive_root(a, n), is_root, "{} mod {}", a, n);
+                if is_root && smallest_root.is_none() {
+                    smallest_root = Some(a);
+                }
+            }
+            assert_eq!(primitive_root(n), smallest_root, "{}", n);
+        }
+    }
+
+    #[test]
+    fn test_large_primes() {
+        assert_eq!(multiplicative_order(2u64, 998244353), Some(499122176));
+        assert_eq!(multiplicative_order(3u64, 4294967291), Some(2147483645));
+        assert_eq!(primitive_root(4294967291u64), Some(2));
+        assert!(!is_primitive_root(3u64, 4294967291));
+        assert_eq!(primitive_root(2 * 3u64.pow(20)), Some(5));
+        // p - 1 has a 43-bit prime factor, out of reach of trial division
+        assert_eq!(primitive_root(18446744073709551557u64), Some(2));
+    }
+}
@@ -0,0 +1,158 @@
+use bpsw::is_strong_lucas_probable_prime;
+use montgomery::Montgomery;
+use reduce::Reducer;
+use strategy::{pow_with, Strategy};
+use word::Word;
+
+/// Primes used for trial division before any Miller–Rabin round
+const SMALL_PRIMES: [u8; 13] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41];
+
+/// Witnesses that decide every `n < 2^32` (Jaeschke)
+const WITNESSES_32: [u64; 3] = [2, 7, 61];
+
+/// Witnesses that decide every `n < 2^64` (Sinclair)
+const WITNESSES_64: [u64; 7] = [2, 325, 9375, 28178, 450775, 9780504, 1795265022];
+
+/// Witnesses that decide every `n < 3.3 * 10^24`, a little over `2^81`
+/// (Sorenson and Webster): the first thirteen primes
+const WITNESSES_128: [u64; 13] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41];
+
+/// The smallest strong pseudoprime to all of [`WITNESSES_128`]
+const WITNESSES_128_BOUND: u128 = 3317044064679887385961981;
+
+/// Tests whether `n` is prime
+///
+/// Small factors are removed by trial division, then the strong probable
+/// prime (Miller–Rabin) test is run in a [`Montgomery`] context for a fixed
+/// set of witnesses chosen by the size of `n`: three below `2^32`, seven below
+/// `2^64` and the first thirteen primes above that. The answer is exact for
+/// every `n` below `3.3 * 10^24`, which covers all of `u8` to `u64`.
+///
+/// Above that bound, only reachable with `u128`, no fixed set of witnesses is
+/// known to be enough, so the witnesses are followed by the strong Lucas test
+/// of [`bpsw`](crate::bpsw). That makes the answer agree with
+/// [`bpsw::is_probable_prime`](crate::bpsw::is_probable_prime), which no
+/// known composite passes.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::is_prime;
+///
+/// assert!(is_prime(65521u16));
+/// assert!(!is_prime(3215031751u32));
+/// assert!(is_prime(0xffff_ffff_ffff_ffc5u64));
+/// ```
+pub fn is_prime<T: Word>(n: T) -> bool {
+    if let Some(answer) = trial_division(n) {
+        return answer;
+    }
+
+    let wide = n.to_u128().unwrap();
+    let witnesses: &[u64] = if wide < 1 << 32 {
+        &WITNESSES_32
+    } else if wide < 1 << 64 {
+        &WITNESSES_64
+    } else {
+        &WITNESSES_128
+    };
+
+    let ctx = Montgomery::new(n);
+    let n_minus_one = n - T::one();
+    let s = n_minus_one.trailing_zeros() as usize;
+    let d = n_minus_one >> s;
+    witnesses.iter().all(|&a| {
+        // Each witness set is only used once n, and so T, is wide enough to
+        // hold all of it
+        let a = T::from(a).unwrap() % n;
+        a.is_zero() || is_strong_probable_prime(&ctx, a, d, s)
+    }) && (wide < WITNESSES_128_BOUND || is_strong_lucas_probable_prime(&ctx))
+}
+
+/// Decides `n` outright if it is below `43^2` or has a prime factor of at
+/// most 41
+pub(crate) fn trial_division<T: Word>(n: T) -> Option<bool> {
+    for &p in &SMALL_PRIMES {
+        let p = T::from(p).unwrap();
+        if n == p {
+            return Some(true);
+        }
+        if (n % p).is_zero() {
+            return Some(false);
+        }
+    }
+    // Any composite below 43^2 has a prime factor of at most 41
+    if T::from(43 * 43).is_none_or(|bound| n < bound) {
+        return Some(n > T::one());
+    }
+    None
+}
+
+/// One Miller–Rabin round: whether `a^d == 1` or `a^(d * 2^r) == -1` for some
+/// `r < s`, where `n - 1 = d * 2^s` with `d` odd
+pub(crate) fn is_strong_probable_prime<T: Word>(ctx: &Montgomery<T>, a: T, d: T, s: usize) -> bool {
+    let one = ctx.one();
+    let minus_one = ctx.modulus() - one;
+    let mut x = pow_with(ctx, Strategy::Auto, ctx.to_montgomery(a), d);
+    if x == one || x == minus_one {
+        return true;
+    }
+    for _ in 1..s {
+        x = ctx.mul(x, x);
+        if x == minus_one {
+            return true;
+        }
+        if x == one {
+            return false;
+        }
+    }
+    false
+}
+
+#[cfg(test)] mod tests {
+    use super::is_prime;
+
+    fn naive(n: u32) -> bool {
+        let n = n as u64;
+        n >= 2 && (2..).take_while(|d| d * d <= n).all(|d| !n.is_multiple_of(d))
+    }
+
+    #[test]
+    fn test_matches_trial_division() {
+        for n in 0..=u16::MAX {
+            assert_eq!(is_prime(n), naive(n as u32), "{}", n);
+            assert_eq!(is_prime(n as u8), naive(n as u8 as u32));
+        }
+        for n in (u32::MAX - 5000)..=u32::MAX {
+            assert_eq!(is_prime(n), naive(n), "{}", n);
+            assert_eq!(is_prime(n as u64), naive(n));
+        }
+    }
+
+    #[test]
+    fn test_pseudoprimes_and_large_primes() {
+        // Strong pseudoprimes to several bases, and a Carmichael number
+        for &n in &[561u128, 2047, 1373653, 25326001, 3215031751, 2152302898747, 3474749660383,
+                    341550071728321, 3825123056546413051, 318665857834031151167461] {
+            assert!(!is_prime(n), "{}", n);
+            if n <= u64::MAX as u128 {
+                assert!(!is_prime(n as u64), "{}", n);
+            }
+        }
+
+        assert!(is_prime(u64::MAX - 58));
+        assert!(!is_prime(u64::MAX));
+        assert!(is_prime((1u128 << 61) - 1));
+        assert!(is_prime((1u128 << 89) - 1));
+        assert!(is_prime(u128::MAX - 158));
+        assert!(!is_prime(((1u128 << 61) - 1) * ((1 << 61) - 1)));
+
+        // The bound itself passes all thirteen witnesses, and only the Lucas
+        // test rules it out
+        let m = 3317044064679887385961981u128;
+        assert!(!is_prime(m));
+        for n in (u128::MAX - 2000)..=u128::MAX {
+            assert_eq!(is_prime(n), ::bpsw::is_probable_prime(n), "{}", n);
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use std::ops::Shr;
+use num::traits::{Num, One, Zero};
+
+use wide::WideningMulMod;
+
+/// A modular multiplication the exponentiation loops run over
+///
+/// Values may be kept in whatever representation the reducer likes (e.g.
+/// Montgomery form), as long as `one` and `mul` agree on it.
+pub(crate) trait Reducer<T> {
+    /// The multiplicative identity
+    fn one(&self) -> T;
+
+    /// Product of two reduced values
+    fn mul(&self, a: T, b: T) -> T;
+}
+
+/// Reduces every product with [`WideningMulMod::mul_mod`]
+pub(crate) struct Plain<T> {
+    pub modulus: T,
+}
+
+impl<T> Reducer<T> for Plain<T> where T: WideningMulMod + One {
+    #[inline]
+    fn one(&self) -> T {
+        T::one()
+    }
+
+    #[inline]
+    fn mul(&self, a: T, b: T) -> T {
+        a.mul_mod(b, self.modulus)
+    }
+}
+
+#[allow(non_snake_case)]
+/// Right-to-left binary exponentiation
+///
+/// `base` must already be reduced into the reducer's representation.
+pub(crate) fn binary_pow<T, R>(reducer: &R, base: T, exponent: T) -> T where T: Num + PartialOrd + Shr<T, Output=T> + Copy, R: Reducer<T> {
+    let ONE: T = One::one();
+    let TWO: T = ONE + ONE;
+    let ZERO: T = Zero::zero();
+
+    let mut result = reducer.one();
+    let mut base = base;
+    let mut exponent = exponent;
+
+    loop {
+        if exponent <= ZERO {
+            break;
+        }
+
+        if exponent % TWO == ONE {
+            result = reducer.mul(result, base);
+        }
+
+        exponent = exponent >> ONE;
+        base = reducer.mul(base, base);
+    }
+
+    result
+}
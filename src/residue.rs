@@ -0,0 +1,251 @@
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+use std::ptr;
+
+use barrett::Barrett;
+use error::ModExpError;
+use gcd::mod_inverse;
+use montgomery::Montgomery;
+use reduce::Reducer;
+use strategy::{pow_with, Strategy};
+use word::Word;
+
+/// A modulus known at runtime, with its reduction precomputed
+///
+/// Odd moduli get a [`Montgomery`] context and even ones a [`Barrett`]
+/// context; [`Residue`]s created from the context borrow it, so arithmetic on
+/// them never needs the modulus passed again.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::ModContext;
+///
+/// let ctx = ModContext::new(497u64);
+/// let a = ctx.residue(4);
+/// let b = ctx.residue(500);
+/// assert_eq!((a * b).value(), 12);
+/// assert_eq!(a.pow(13).value(), 445);
+/// assert_eq!((a - b).value(), 1);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModContext<T> {
+    /// Residues are kept in Montgomery form
+    Montgomery(Montgomery<T>),
+    /// Residues are kept as ordinary integers
+    Barrett(Barrett<T>),
+}
+
+impl<T: Word> ModContext<T> {
+    /// Builds the context for `modulus`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the modulus is zero or one
+    pub fn new(modulus: T) -> ModContext<T> {
+        match ModContext::checked_new(modulus) {
+            Ok(ctx) => ctx,
+            Err(e) => panic!("ModContext::new: {}", e),
+        }
+    }
+
+    /// Builds the context for `modulus`, or reports why it can't be used
+    pub fn checked_new(modulus: T) -> Result<ModContext<T>, ModExpError> {
+        if modulus & T::one() == T::one() {
+            Montgomery::checked_new(modulus).map(ModContext::Montgomery)
+        } else {
+            Barrett::checked_new(modulus).map(ModContext::Barrett)
+        }
+    }
+
+    /// The modulus
+    pub fn modulus(&self) -> T {
+        match *self {
+            ModContext::Montgomery(ref ctx) => ctx.modulus(),
+            ModContext::Barrett(ref ctx) => ctx.modulus(),
+        }
+    }
+
+    /// The residue of `x` modulo this context's modulus
+    pub fn residue(&self, x: T) -> Residue<'_, T> {
+        Residue { ctx: self, repr: self.enter(x) }
+    }
+
+    /// Converts `x` into the representation residues are kept in
+    pub(crate) fn enter(&self, x: T) -> T {
+        match *self {
+            ModContext::Montgomery(ref ctx) => ctx.to_montgomery(x),
+            ModContext::Barrett(ref ctx) => x % ctx.modulus(),
+        }
+    }
+
+    /// Converts `repr` back out of the representation residues are kept in
+    pub(crate) fn leave(&self, repr: T) -> T {
+        match *self {
+            ModContext::Montgomery(ref ctx) => ctx.from_montgomery(repr),
+            ModContext::Barrett(_) => repr,
+        }
+    }
+}
+
+impl<T: Word> Reducer<T> for ModContext<T> {
+    #[inline]
+    fn one(&self) -> T {
+        match *self {
+            ModContext::Montgomery(ref ctx) => ctx.one(),
+            ModContext::Barrett(ref ctx) => ctx.one(),
+        }
+    }
+
+    #[inline]
+    fn mul(&self, a: T, b: T) -> T {
+        match *self {
+            ModContext::Montgomery(ref ctx) => Montgomery::mul(ctx, a, b),
+            ModContext::Barrett(ref ctx) => ctx.mul_mod(a, b),
+        }
+    }
+}
+
+/// An integer modulo the modulus of the [`ModContext`] it was created from
+///
+/// Supports `+`, `-`, `*` and unary `-` with other residues of the same
+/// context. The operators panic when the two sides come from contexts with
+/// different moduli; the `checked_*` methods return
+/// [`ModExpError::ContextMismatch`] instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Residue<'a, T: 'a> {
+    ctx: &'a ModContext<T>,
+    /// The value in the context's representation
+    repr: T,
+}
+
+impl<'a, T: Word> Residue<'a, T> {
+    /// The context this residue belongs to
+    pub fn context(&self) -> &'a ModContext<T> {
+        self.ctx
+    }
+
+    /// The residue as an ordinary integer in `[0, modulus)`
+    pub fn value(&self) -> T {
+        self.ctx.leave(self.repr)
+    }
+
+    /// Raises the residue to `exponent`
+    pub fn pow(&self, exponent: T) -> Residue<'a, T> {
+        self.with(pow_with(self.ctx, Strategy::Auto, self.repr, exponent))
+    }
+
+    /// The multiplicative inverse, if the residue is coprime to the modulus
+    pub fn inverse(&self) -> Option<Residue<'a, T>> {
+        mod_inverse(self.value(), self.ctx.modulus()).map(|x| self.ctx.residue(x))
+    }
+
+    /// `self + rhs`, or an error if they belong to different contexts
+    pub fn checked_add(&self, rhs: &Residue<'a, T>) -> Result<Residue<'a, T>, ModExpError> {
+        let n = self.same_context(rhs)?;
+        let (sum, carry) = self.repr.overflowing_add(rhs.repr);
+        Ok(self.with(if carry || sum >= n { sum.wrapping_sub(n) } else { sum }))
+    }
+
+    /// `self - rhs`, or an error if they belong to different contexts
+    pub fn checked_sub(&self, rhs: &Residue<'a, T>) -> Result<Residue<'a, T>, ModExpError> {
+        let n = self.same_context(rhs)?;
+        let (diff, borrow) = self.repr.overflowing_sub(rhs.repr);
+        Ok(self.with(if borrow { diff.wrapping_add(n) } else { diff }))
+    }
+
+    /// `self * rhs`, or an error if they belong to different contexts
+    pub fn checked_mul(&self, rhs: &Residue<'a, T>) -> Result<Residue<'a, T>, ModExpError> {
+        self.same_context(rhs)?;
+        Ok(self.with(self.ctx.mul(self.repr, rhs.repr)))
+    }
+
+    fn with(&self, repr: T) -> Residue<'a, T> {
+        Residue { ctx: self.ctx, repr }
+    }
+
+    /// The shared modulus, if both residues use the same context
+    fn same_context(&self, rhs: &Residue<'a, T>) -> Result<T, ModExpError> {
+        if ptr::eq(self.ctx, rhs.ctx) || self.ctx == rhs.ctx {
+            Ok(self.ctx.modulus())
+        } else {
+            Err(ModExpError::ContextMismatch)
+        }
+    }
+}
+
+impl<'a, T: Word + fmt::Display> fmt::Display for Residue<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.value(), f)
+    }
+}
+
+macro_rules! residue_op_impl {
+    ($($trait_:ident::$method:ident => $checked:ident),*) => {$(
+        impl<'a, T: Word> $trait_ for Residue<'a, T> {
+            type Output = Residue<'a, T>;
+
+            fn $method(self, rhs: Residue<'a, T>) -> Residue<'a, T> {
+                match self.$checked(&rhs) {
+                    Ok(result) => result,
+                    Err(e) => panic!("Residue::{}: {}", stringify!($method), e),
+                }
+            }
+        }
+    )*}
+}
+
+residue_op_impl!(Add::add => checked_add, Sub::sub => checked_sub, Mul::mul => checked_mul);
+
+impl<'a, T: Word> Neg for Residue<'a, T> {
+    type Output = Residue<'a, T>;
+
+    fn neg(self) -> Residue<'a, T> {
+        if self.repr.is_zero() {
+            self
+        } else {
+            self.with(self.ctx.modulus() - self.repr)
+        }
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::ModContext;
+    use {mod_exp, ModExpError};
+
+    #[test]
+    fn test_matches_plain_arithmetic() {
+        for &n in &[2u64, 97, 1 << 40, 18446744073709551557, u64::MAX - 1] {
+            let ctx = ModContext::new(n);
+            let values = [0, 1, 2, n / 3, n - 2, n - 1, 0xdead_beef_cafe];
+            for &a in &values {
+                for &b in &values {
+                    let (x, y) = (ctx.residue(a), ctx.residue(b));
+                    let (a, b, m) = (a as u128 % n as u128, b as u128 % n as u128, n as u128);
+                    assert_eq!((x + y).value() as u128, (a + b) % m);
+                    assert_eq!((x - y).value() as u128, (a + m - b) % m);
+                    assert_eq!((x * y).value() as u128, a * b % m);
+                    assert_eq!((-x + x).value(), 0);
+                }
+                assert_eq!(ctx.residue(a).pow(12345).value(), mod_exp(a, 12345, n));
+                if let Some(inverse) = ctx.residue(a).inverse() {
+                    assert_eq!((inverse * ctx.residue(a)).value(), 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_context_mismatch() {
+        let (a, b, c) = (ModContext::new(11u32), ModContext::new(13u32), ModContext::new(11u32));
+        assert_eq!(a.residue(3).checked_mul(&b.residue(3)), Err(ModExpError::ContextMismatch));
+        assert_eq!(a.residue(3).checked_add(&c.residue(9)).map(|r| r.value()), Ok(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Residue::sub: residues belong to different contexts")]
+    fn test_mismatch_panics() {
+        let (a, b) = (ModContext::new(11u32), ModContext::new(12u32));
+        let _ = a.residue(3) - b.residue(3);
+    }
+}
@@ -0,0 +1,471 @@
+use std::ops::Shr;
+use num::traits::{checked_pow, CheckedMul, Num, One, Zero};
+
+use crt::crt;
+use error::ModExpError;
+use gcd::{add_mod, mod_inverse, sub_mod};
+use symbol::legendre;
+use wide::WideningMulMod;
+use {check_modulus, mod_exp, rem_euclid};
+
+#[allow(non_snake_case)]
+/// Computes the square roots of `a` modulo a prime `p`
+///
+/// Returns both roots, smaller first, or `None` if `a` is not a square modulo
+/// `p`; when `p` divides `a` the only root is zero, returned twice. Primes
+/// with `p == 3 (mod 4)` or `p == 5 (mod 8)` take a single [`mod_exp`];
+/// others use the Tonelli–Shanks algorithm, which needs a few more
+/// exponentiations when `p - 1` is divisible by a large power of two. See
+/// [`sqrt_mod_cipolla`] for an alternative that doesn't depend on that.
+///
+/// The primality of `p` is not checked. For a composite `p` the result is
+/// meaningless, and may be `None` even when roots exist.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::sqrt_mod;
+///
+/// assert_eq!(sqrt_mod(2, 7), Some((3, 4)));
+/// assert_eq!(sqrt_mod(3, 7), None);
+/// assert_eq!(sqrt_mod(-1i64, 13), Some((5, 8)));
+/// ```
+///
+/// # Panics
+///
+/// Panics if `p` is zero, one, negative or even but not two
+pub fn sqrt_mod<T>(a: T, p: T) -> Option<(T, T)> where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod {
+    let ONE: T = One::one();
+    let TWO = ONE + ONE;
+    let THREE = TWO + ONE;
+    let FOUR = TWO + TWO;
+    let EIGHT = FOUR + FOUR;
+
+    let a = match reduce_for_prime(a, p, "sqrt_mod") {
+        Ok(a) => a,
+        Err(root) => return root,
+    };
+
+    let root = if p % FOUR == THREE {
+        mod_exp(a, (p >> TWO) + ONE, p)
+    } else if p % EIGHT == FOUR + ONE {
+        // Atkin's method: with v = (2a)^((p-5)/8) and i = 2av^2, i is a
+        // square root of -1 and av(i - 1) one of a
+        let two_a = add_mod(a, a, p);
+        let v = mod_exp(two_a, p >> THREE, p);
+        let i = two_a.mul_mod(v.mul_mod(v, p), p);
+        a.mul_mod(v, p).mul_mod(sub_mod(i, ONE, p), p)
+    } else {
+        tonelli_shanks(a, p)?
+    };
+    Some(ordered(root, p))
+}
+
+#[allow(non_snake_case)]
+/// Computes the square roots of `a` modulo a prime `p` with Cipolla's
+/// algorithm
+///
+/// Gives the same result as [`sqrt_mod`]. Cipolla's algorithm finds a `t`
+/// for which `t^2 - a` is not a square and raises `t + sqrt(t^2 - a)` to the
+/// power `(p + 1) / 2` in the field with `p^2` elements, so it takes one
+/// exponentiation however large the power of two dividing `p - 1` is.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::sqrt_mod_cipolla;
+///
+/// // 2^32 divides p - 1, the worst case for Tonelli–Shanks
+/// let p = 0xffff_ffff_0000_0001u64;
+/// let (r, s) = sqrt_mod_cipolla(5, p).unwrap();
+/// assert_eq!((r as u128 * r as u128 % p as u128, r + s), (5, p));
+/// ```
+///
+/// # Panics
+///
+/// Panics if `p` is zero, one, negative or even but not two
+pub fn sqrt_mod_cipolla<T>(a: T, p: T) -> Option<(T, T)> where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod {
+    let ONE: T = One::one();
+    let ZERO: T = Zero::zero();
+    let TWO = ONE + ONE;
+
+    let a = match reduce_for_prime(a, p, "sqrt_mod_cipolla") {
+        Ok(a) => a,
+        Err(root) => return root,
+    };
+
+    let mut t = ZERO;
+    let w = loop {
+        let w = sub_mod(t.mul_mod(t, p), a, p);
+        if legendre(w, p) == -1 {
+            break w;
+        }
+        t = t + ONE;
+        if t == p {
+            return None;
+        }
+    };
+
+    // Elements x + y * omega of F_p[omega] with omega^2 = w
+    let mul = |(x1, y1): (T, T), (x2, y2): (T, T)| (
+        add_mod(x1.mul_mod(x2, p), y1.mul_mod(y2, p).mul_mod(w, p), p),
+        add_mod(x1.mul_mod(y2, p), y1.mul_mod(x2, p), p),
+    );
+    let mut result = (ONE, ZERO);
+    let mut base = (t, ONE);
+    let mut exponent = (p >> ONE) + ONE;
+    while exponent > ZERO {
+        if exponent % TWO == ONE {
+            result = mul(result, base);
+        }
+        exponent = exponent >> ONE;
+        base = mul(base, base);
+    }
+    Some(ordered(result.0, p))
+}
+
+#[allow(non_snake_case)]
+/// Computes the square roots of `a` modulo `p^k`, for a prime `p`
+///
+/// Returns `(roots, m)`, with `m` a divisor of `p^k`: the square roots of `a`
+/// modulo `p^k` are exactly the numbers congruent to one of `roots` modulo
+/// `m`. The roots are in increasing order and below `m`, and there are at
+/// most four of them, however many roots `a` has modulo `p^k`.
+///
+/// For `a` coprime to `p`, `m` is `p^k` itself, and the roots modulo `p` are
+/// lifted with Newton's iteration (Hensel's lemma), which doubles the power
+/// of `p` they are correct to at every step. An odd `p` gives two roots or
+/// none. For `p == 2` there is one root modulo 2, two modulo 4 and four
+/// modulo higher powers, lifted one bit at a time.
+///
+/// When `p` divides `a`, a root exists only if `a` is zero modulo `p^k` or
+/// `a = p^(2j) * u` with `u` a square coprime to `p`. The roots are then the
+/// multiples of `p^ceil(k/2)`, with `m = p^ceil(k/2)` and the single root
+/// zero, or `p^j` times the roots of `u` modulo `p^(k-2j)`, with
+/// `m = p^(k-j)`.
+///
+/// `roots` is empty if `a` is not a square, with `m` equal to `p^k`.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::sqrt_mod_prime_power;
+///
+/// assert_eq!(sqrt_mod_prime_power(2, 7, 3), (vec![108, 235], 343));
+/// assert_eq!(sqrt_mod_prime_power(17u32, 2, 5), (vec![7, 9, 23, 25], 32));
+/// assert!(sqrt_mod_prime_power(3, 5, 4).0.is_empty());
+/// // The roots of 0 modulo 27 are 0, 9 and 18
+/// assert_eq!(sqrt_mod_prime_power(0, 3, 3), (vec![0], 9));
+/// // The roots of 9 modulo 27 are 3, 6, 12, 15, 21 and 24
+/// assert_eq!(sqrt_mod_prime_power(9, 3, 3), (vec![3, 6], 9));
+/// ```
+///
+/// # Panics
+///
+/// Panics if `p^k` is less than two or doesn't fit in `T`, or if `p` is even
+/// but not two
+pub fn sqrt_mod_prime_power<T>(a: T, p: T, k: u32) -> (Vec<T>, T) where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod + CheckedMul {
+    let ZERO: T = Zero::zero();
+
+    let n = match checked_pow(p, k as usize) {
+        Some(n) => n,
+        None => panic!("sqrt_mod_prime_power: {}", ModExpError::Overflow),
+    };
+    if let Err(e) = check_modulus(n) {
+        panic!("sqrt_mod_prime_power: {}", e);
+    }
+    let mut a = rem_euclid(a, n);
+
+    // a = p^v * u with u coprime to p, or v = k for a zero a
+    let mut v = 0;
+    while v < k && (a % p).is_zero() {
+        a = a / p;
+        v += 1;
+    }
+    if v % 2 == 1 && v < k {
+        return (vec![], n);
+    }
+
+    // With v = 2j < k, x = p^j * y is a root exactly when y^2 == u
+    // (mod p^(k-2j)), which only pins x down modulo p^(k-j). A zero a just
+    // needs p^ceil(k/2) to divide x.
+    let j = v.div_ceil(2);
+    if v == k {
+        return (vec![ZERO], checked_pow(p, j as usize).unwrap());
+    }
+    let scale = checked_pow(p, j as usize).unwrap();
+    let roots = sqrt_mod_unit(a, p, k - v).into_iter().map(|y| y * scale).collect();
+    (roots, checked_pow(p, (k - j) as usize).unwrap())
+}
+
+#[allow(non_snake_case)]
+/// The roots of `a` modulo `p^k` for `a` coprime to `p`, in increasing order
+fn sqrt_mod_unit<T>(a: T, p: T, k: u32) -> Vec<T> where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod + CheckedMul {
+    let ONE: T = One::one();
+    let TWO = ONE + ONE;
+    let FOUR = TWO + TWO;
+    let EIGHT = FOUR + FOUR;
+
+    let n = checked_pow(p, k as usize).unwrap();
+    if p == TWO {
+        if k == 1 {
+            return vec![ONE];
+        }
+        if k == 2 {
+            return if a % FOUR == ONE { vec![ONE, n - ONE] } else { vec![] };
+        }
+        if a % EIGHT != ONE {
+            return vec![];
+        }
+        // If x^2 == a (mod 2^j) but not mod 2^(j+1), then
+        // (x + 2^(j-1))^2 == a (mod 2^(j+1))
+        let mut x = ONE;
+        let mut step = FOUR;
+        for _ in 3..k {
+            let m = step * FOUR;
+            if x.mul_mod(x, m) != a % m {
+                x = x + step;
+            }
+            step = step * TWO;
+        }
+        let half = n >> ONE;
+        let mut roots = vec![x, n - x, add_mod(x, half, n), sub_mod(n - x, half, n)];
+        roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        return roots;
+    }
+
+    let mut x = match sqrt_mod(a % p, p) {
+        Some((root, _)) => root,
+        None => return vec![],
+    };
+    let mut precision = 1;
+    while precision < k {
+        // x -= (x^2 - a) / 2x, where 2x is invertible since p is odd and
+        // doesn't divide x
+        let f = sub_mod(x.mul_mod(x, n), a, n);
+        let inverse = mod_inverse(add_mod(x, x, n), n).unwrap();
+        x = sub_mod(x, f.mul_mod(inverse, n), n);
+        precision *= 2;
+    }
+    let (r, s) = ordered(x, n);
+    vec![r, s]
+}
+
+#[allow(non_snake_case)]
+/// Computes the square roots of `a` modulo `n`, given the factorization of
+/// `n` as `(prime, exponent)` pairs
+///
+/// Returns `(roots, m)` as [`sqrt_mod_prime_power`] does, with `m` a divisor
+/// of `n`: the roots modulo `n` are the numbers congruent to one of `roots`
+/// modulo `m`. The roots modulo each prime power are joined with the Chinese
+/// remainder theorem, so for `r` distinct odd prime factors there are at most
+/// `2^r` of them (twice or four times that when `n` is even). They are
+/// returned in increasing order, and `roots` is empty if `a` is not a square
+/// modulo one of the prime powers.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::sqrt_mod_composite;
+///
+/// // 91 = 7 * 13
+/// assert_eq!(sqrt_mod_composite(4, &[(7, 1), (13, 1)]), (vec![2, 37, 54, 89], 91));
+/// assert!(sqrt_mod_composite(5, &[(7, 1), (13, 1)]).0.is_empty());
+/// // The roots of 0 modulo 63 = 3^2 * 7 are the multiples of 21
+/// assert_eq!(sqrt_mod_composite(0, &[(3, 2), (7, 1)]), (vec![0], 21));
+/// ```
+///
+/// # Panics
+///
+/// Panics if `n` doesn't fit in `T`, if the factors are not distinct primes,
+/// and whenever [`sqrt_mod_prime_power`] would for one of the factors
+pub fn sqrt_mod_composite<T>(a: T, factors: &[(T, u32)]) -> (Vec<T>, T) where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod + CheckedMul {
+    let ONE: T = One::one();
+    let ZERO: T = Zero::zero();
+
+    let mut roots = vec![ZERO];
+    let mut modulus = ONE;
+    let mut n = ONE;
+    for &(p, k) in factors {
+        let (prime_power_roots, m) = sqrt_mod_prime_power(a, p, k);
+        // sqrt_mod_prime_power has checked p^k fits
+        n = match n.checked_mul(&checked_pow(p, k as usize).unwrap()) {
+            Some(product) => product,
+            None => panic!("sqrt_mod_composite: {}", ModExpError::Overflow),
+        };
+        let mut combined = Vec::with_capacity(roots.len() * prime_power_roots.len());
+        for &r in &roots {
+            for &s in &prime_power_roots {
+                match crt(&[(r, modulus), (s, m)]) {
+                    Some((x, _)) => combined.push(x),
+                    None => panic!("sqrt_mod_composite: {}", ModExpError::InvalidFactorization),
+                }
+            }
+        }
+        roots = combined;
+        // m divides p^k, so this fits when n does
+        modulus = modulus * m;
+    }
+    if roots.is_empty() {
+        return (roots, n);
+    }
+    roots.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    (roots, modulus)
+}
+
+#[allow(non_snake_case)]
+/// Validates `p` and reduces `a` modulo it, or gives the answer straight away
+/// when `a` is zero, `p` is two, or `a` is not a square
+fn reduce_for_prime<T>(a: T, p: T, name: &str) -> Result<T, Option<(T, T)>> where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod {
+    let ONE: T = One::one();
+    let TWO = ONE + ONE;
+
+    if let Err(e) = check_modulus(p) {
+        panic!("{}: {}", name, e);
+    }
+    let a = rem_euclid(a, p);
+    if a.is_zero() || p == TWO {
+        return Err(Some((a, a)));
+    }
+    if (p % TWO).is_zero() {
+        panic!("{}: {}", name, ModExpError::EvenModulus);
+    }
+    if legendre(a, p) != 1 {
+        return Err(None);
+    }
+    Ok(a)
+}
+
+#[allow(non_snake_case)]
+/// The Tonelli–Shanks algorithm for a quadratic residue `a`, or `None` if it
+/// finds `p` is not prime after all
+fn tonelli_shanks<T>(a: T, p: T) -> Option<T> where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod {
+    let ONE: T = One::one();
+    let TWO = ONE + ONE;
+
+    // p - 1 = q * 2^s with q odd
+    let mut q = p - ONE;
+    let mut s = 0;
+    while (q % TWO).is_zero() {
+        q = q >> ONE;
+        s += 1;
+    }
+
+    let mut z = TWO;
+    while legendre(z, p) != -1 {
+        z = z + ONE;
+        if z == p {
+            return None;
+        }
+    }
+
+    // Invariants: r^2 == a * t, c^(2^(m-1)) == -1 and t^(2^(m-1)) == 1
+    let mut c = mod_exp(z, q, p);
+    let mut r = mod_exp(a, (q >> ONE) + ONE, p);
+    let mut t = mod_exp(a, q, p);
+    let mut m = s;
+    while t != ONE {
+        // The least i with t^(2^i) == 1
+        let mut i = 0;
+        let mut power = t;
+        while power != ONE {
+            power = power.mul_mod(power, p);
+            i += 1;
+            if i == m {
+                return None;
+            }
+        }
+        let mut b = c;
+        for _ in 0..m - i - 1 {
+            b = b.mul_mod(b, p);
+        }
+        r = r.mul_mod(b, p);
+        c = b.mul_mod(b, p);
+        t = t.mul_mod(c, p);
+        m = i;
+    }
+    Some(r)
+}
+
+/// The pair `(root, n - root)`, smaller first
+fn ordered<T>(root: T, n: T) -> (T, T) where T: Num + PartialOrd + Copy {
+    if root.is_zero() {
+        return (root, root);
+    }
+    let other = n - root;
+    if root <= other { (root, other) } else { (other, root) }
+}
+
+#[cfg(test)] mod tests {
+    use super::{sqrt_mod, sqrt_mod_cipolla, sqrt_mod_composite, sqrt_mod_prime_power};
+    use is_prime;
+
+    /// Every root modulo `n`, from the roots modulo `m` that stand for them
+    fn all_roots((roots, m): (Vec<u64>, u64), n: u64) -> Vec<u64> {
+        assert!(n.is_multiple_of(m) && roots.iter().all(|&r| r < m));
+        (0..n).filter(|x| roots.contains(&(x % m))).collect()
+    }
+
+    #[test]
+    fn test_matches_brute_force_for_primes() {
+        for p in (2..300u64).filter(|&p| is_prime(p)) {
+            for a in 0..p {
+                let roots: Vec<u64> = (0..p).filter(|&x| x * x % p == a).collect();
+                let expected = match roots.len() {
+                    0 => None,
+                    1 => Some((roots[0], roots[0])),
+                    _ => Some((roots[0], roots[1])),
+                };
+                assert_eq!(sqrt_mod(a, p), expected, "sqrt({}) mod {}", a, p);
+                assert_eq!(sqrt_mod_cipolla(a, p), expected, "sqrt({}) mod {}", a, p);
+                assert_eq!(sqrt_mod(a as i32 - p as i32, p as i32), expected.map(|(r, s)| (r as i32, s as i32)));
+            }
+        }
+
+        // p - 1 is divisible by 2^32, 2^64 and 2^4 respectively
+        for &p in &[0xffff_ffff_0000_0001u128, 0x7fff_ffff_ffff_ffff_0000_0000_0000_0001, u128::MAX - 158] {
+            if !is_prime(p) {
+                continue;
+            }
+            for &a in &[2u128, 3, 5, 0xdead_beef, p - 1, p / 3] {
+                let root = sqrt_mod(a, p);
+                assert_eq!(root, sqrt_mod_cipolla(a, p));
+                if let Some((r, s)) = root {
+                    assert_eq!(::WideningMulMod::mul_mod(r, r, p), a);
+                    assert_eq!(r + s, p);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_prime_powers_and_composites() {
+        for &(p, k) in &[(2u64, 1u32), (2, 2), (2, 3), (2, 7), (3, 1), (3, 5), (5, 3), (7, 2), (13, 2)] {
+            let n = p.pow(k);
+            for a in 0..n {
+                let roots: Vec<u64> = (0..n).filter(|&x| x * x % n == a).collect();
+                assert_eq!(all_roots(sqrt_mod_prime_power(a, p, k), n), roots, "sqrt({}) mod {}^{}", a, p, k);
+            }
+        }
+
+        let factors = [(2u64, 3u32), (3, 2), (5, 1), (7, 1)];
+        let n = 2520;
+        for a in 0..n {
+            let roots: Vec<u64> = (0..n).filter(|&x| x * x % n == a).collect();
+            assert_eq!(all_roots(sqrt_mod_composite(a, &factors), n), roots, "sqrt({}) mod {}", a, n);
+        }
+        assert_eq!(sqrt_mod_composite(0u64, &[(7, 1), (13, 1)]), (vec![0], 91));
+        assert_eq!(sqrt_mod_composite(-63i32, &[(2, 4), (3, 3)]), sqrt_mod_composite(369, &[(2, 4), (3, 3)]));
+
+        let p = 0xffff_ffff_ffff_ffc5u128;
+        let x = 0x1234_5678_9abc_def0_1234_5678u128;
+        let (roots, m) = sqrt_mod_prime_power(::WideningMulMod::mul_mod(x, x, p * p), p, 2);
+        assert!(roots.contains(&x) && m == p * p);
+
+        // p^(k/2) roots, but only one of them is listed
+        assert_eq!(sqrt_mod_prime_power(0u64, 4294967291, 2), (vec![0], 4294967291));
+        let q = 4294967291u128;
+        assert_eq!(sqrt_mod_prime_power(4 * q * q, q, 4), (vec![2 * q, (q * q - 2) * q], q.pow(3)));
+        assert_eq!(sqrt_mod_composite(0u64, &[(1000003, 2), (3, 3)]), (vec![0], 1000003 * 9));
+    }
+}
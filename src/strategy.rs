@@ -0,0 +1,189 @@
+use std::{cmp, mem};
+use std::ops::Shr;
+use num::traits::{Num, One, Zero};
+
+use reduce::{binary_pow, Reducer};
+
+/// Largest window size the windowed strategies will use
+const MAX_WINDOW: u32 = 16;
+
+/// How the exponent is scanned during an exponentiation
+///
+/// The windowed strategies work left-to-right and trade a table of
+/// precomputed powers of the base for fewer multiplications; they pay off for
+/// exponents with many bits.
+///
+/// Window sizes outside `1..=16` are clamped into that range.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Right-to-left square-and-multiply, one bit at a time
+    Binary,
+    /// Left-to-right k-ary method, consuming `k` bits per table lookup
+    FixedWindow(u32),
+    /// Left-to-right sliding window of up to `k` bits, with a table of odd
+    /// powers only
+    SlidingWindow(u32),
+    /// A sliding window sized from the bit length of the exponent
+    #[default]
+    Auto,
+}
+
+/// Window size `Strategy::Auto` picks for an exponent of `bits` bits
+///
+/// Smaller exponents don't make up for the cost of building a table, and get
+/// a window of one, i.e. plain square-and-multiply.
+pub(crate) fn auto_window(bits: usize) -> u32 {
+    match bits {
+        0..=23 => 1,
+        24..=79 => 3,
+        80..=239 => 4,
+        240..=671 => 5,
+        _ => 6,
+    }
+}
+
+/// Exponentiation of an already reduced `base` with the given strategy
+pub(crate) fn pow_with<T, R>(reducer: &R, strategy: Strategy, base: T, exponent: T) -> T where T: Num + PartialOrd + Shr<T, Output=T> + Copy, R: Reducer<T> {
+    if let Strategy::Binary = strategy {
+        return binary_pow(reducer, base, exponent);
+    }
+
+    let bits = exponent_bits(exponent);
+    let (sliding, k) = match strategy {
+        Strategy::FixedWindow(k) => (false, k),
+        Strategy::SlidingWindow(k) => (true, k),
+        _ => (true, auto_window(bits.len())),
+    };
+    let k = k.clamp(1, MAX_WINDOW) as usize;
+
+    if sliding {
+        sliding_window_pow(reducer, base, &bits, k)
+    } else {
+        fixed_window_pow(reducer, base, &bits, k)
+    }
+}
+
+#[allow(non_snake_case)]
+/// Bits of a non-negative exponent, least significant first, without
+/// leading zeros
+pub(crate) fn exponent_bits<T>(exponent: T) -> Vec<bool> where T: Num + PartialOrd + Shr<T, Output=T> + Copy {
+    let ONE: T = One::one();
+    let TWO: T = ONE + ONE;
+    let ZERO: T = Zero::zero();
+
+    let mut bits = Vec::with_capacity(8 * mem::size_of::<T>());
+    let mut exponent = exponent;
+    while exponent > ZERO {
+        bits.push(exponent % TWO == ONE);
+        exponent = exponent >> ONE;
+    }
+    bits
+}
+
+/// Value of the bits in `lo..hi` (least significant first) as a table index
+pub(crate) fn window_value(bits: &[bool], lo: usize, hi: usize) -> usize {
+    (lo..cmp::min(hi, bits.len())).rev().fold(0, |acc, i| (acc << 1) | bits[i] as usize)
+}
+
+fn fixed_window_pow<T: Copy, R: Reducer<T>>(reducer: &R, base: T, bits: &[bool], k: usize) -> T {
+    let mut table = Vec::with_capacity(1 << k);
+    table.push(reducer.one());
+    for i in 1..(1 << k) {
+        let next = reducer.mul(table[i - 1], base);
+        table.push(next);
+    }
+
+    let mut result = reducer.one();
+    let windows = bits.len().div_ceil(k);
+    for w in (0..windows).rev() {
+        if w + 1 != windows {
+            for _ in 0..k {
+                result = reducer.mul(result, result);
+            }
+        }
+
+        let digit = window_value(bits, w * k, (w + 1) * k);
+        if digit != 0 {
+            result = reducer.mul(result, table[digit]);
+        }
+    }
+    result
+}
+
+fn sliding_window_pow<T: Copy, R: Reducer<T>>(reducer: &R, base: T, bits: &[bool], k: usize) -> T {
+    // table[i] = base^(2i + 1)
+    let square = reducer.mul(base, base);
+    let mut table = Vec::with_capacity(1 << (k - 1));
+    table.push(base);
+    for i in 1..(1 << (k - 1)) {
+        let next = reducer.mul(table[i - 1], square);
+        table.push(next);
+    }
+
+    let mut result = reducer.one();
+    let mut hi = bits.len();
+    while hi > 0 {
+        if !bits[hi - 1] {
+            result = reducer.mul(result, result);
+            hi -= 1;
+            continue;
+        }
+
+        // The longest window of at most k bits that starts at the top set bit
+        // and ends on a set bit.
+        let mut lo = hi.saturating_sub(k);
+        while !bits[lo] {
+            lo += 1;
+        }
+
+        for _ in lo..hi {
+            result = reducer.mul(result, result);
+        }
+        result = reducer.mul(result, table[window_value(bits, lo, hi) >> 1]);
+        hi = lo;
+    }
+    result
+}
+
+#[cfg(test)] mod tests {
+    use super::{auto_window, Strategy};
+    use {mod_exp, mod_exp_with, Barrett, Montgomery};
+
+    #[test]
+    fn test_strategies_agree() {
+        let strategies = [
+            Strategy::Binary,
+            Strategy::Auto,
+            Strategy::FixedWindow(0),
+            Strategy::FixedWindow(1),
+            Strategy::FixedWindow(3),
+            Strategy::FixedWindow(5),
+            Strategy::SlidingWindow(1),
+            Strategy::SlidingWindow(2),
+            Strategy::SlidingWindow(4),
+            Strategy::SlidingWindow(99),
+        ];
+
+        for &(base, modulus) in &[(7u64, 1000000007u64), (3, 1 << 40), (u64::MAX - 1, u64::MAX)] {
+            for &exponent in &[0u64, 1, 2, 0b1011_0001, 0xdead_beef_cafe, u64::MAX] {
+                let expected = mod_exp(base, exponent, modulus);
+                for &strategy in &strategies {
+                    assert_eq!(mod_exp_with(strategy, base, exponent, modulus), expected);
+                    assert_eq!(Barrett::new(modulus).pow_mod_with(strategy, base, exponent), expected);
+                }
+            }
+        }
+
+        let ctx = Montgomery::new(1000000007u64);
+        assert_eq!(ctx.pow_with(Strategy::SlidingWindow(5), 7, u64::MAX), mod_exp(7, u64::MAX, 1000000007));
+        assert_eq!(mod_exp_with(Strategy::FixedWindow(4), -3i32, 77, 1000), mod_exp(-3i32, 77, 1000));
+    }
+
+    #[test]
+    fn test_auto_window() {
+        assert_eq!(auto_window(0), 1);
+        assert_eq!(auto_window(64), 3);
+        assert_eq!(auto_window(128), 4);
+        assert_eq!(auto_window(4096), 6);
+    }
+}
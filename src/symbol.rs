@@ -0,0 +1,223 @@
+use std::ops::Shr;
+use num::traits::{Num, One, Zero};
+
+use error::ModExpError;
+use wide::WideningMulMod;
+use rem_euclid;
+
+#[allow(non_snake_case)]
+/// Computes the Jacobi symbol `(a/n)` for a positive odd `n`
+///
+/// The result is `0` when `a` and `n` share a factor, and otherwise the
+/// product of the Legendre symbols of `a` modulo the prime factors of `n`.
+/// Uses the binary algorithm: factors of two are pulled out of `a` and
+/// reciprocity swaps the arguments, so only remainders are needed and no
+/// exponentiation at all. `a` may be negative.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::jacobi;
+///
+/// assert_eq!(jacobi(1001, 9907), -1);
+/// assert_eq!(jacobi(-2i32, 15), -1);
+/// assert_eq!(jacobi(12u64, 15), 0);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `n` is zero, negative or even
+pub fn jacobi<T>(a: T, n: T) -> i32 where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod {
+    let ONE: T = One::one();
+    let ZERO: T = Zero::zero();
+    let TWO = ONE + ONE;
+
+    if n.is_zero() {
+        panic!("jacobi: {}", ModExpError::ZeroModulus);
+    }
+    if n < ZERO {
+        panic!("jacobi: {}", ModExpError::NegativeModulus);
+    }
+    if n % TWO == ZERO {
+        panic!("jacobi: {}", ModExpError::EvenModulus);
+    }
+
+    let (mut a, mut n) = (rem_euclid(a, n), n);
+    let mut result = 1;
+    while !a.is_zero() {
+        // (2/n) is -1 exactly when n == 3 or 5 (mod 8)
+        let n_mod_8 = low_bits(n, 3);
+        while a % TWO == ZERO {
+            a = a >> ONE;
+            if n_mod_8 == 3 || n_mod_8 == 5 {
+                result = -result;
+            }
+        }
+        // Reciprocity: (a/n) = -(n/a) when both are 3 (mod 4)
+        if low_bits(a, 2) == 3 && n_mod_8 % 4 == 3 {
+            result = -result;
+        }
+        let r = n % a;
+        n = a;
+        a = r;
+    }
+    if n.is_one() { result } else { 0 }
+}
+
+/// Computes the Legendre symbol `(a/p)` for an odd prime `p`
+///
+/// `1` if `a` is a non-zero square modulo `p`, `-1` if it is not a square and
+/// `0` if `p` divides `a`. Equal to `a^((p-1)/2) mod p` by Euler's criterion,
+/// but computed with [`jacobi`], which is much faster. Primality of `p` is not
+/// checked; for a composite `p` the result is the Jacobi symbol.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::legendre;
+///
+/// assert_eq!(legendre(2, 7), 1);
+/// assert_eq!(legendre(3, 7), -1);
+/// assert_eq!(legendre(14, 7), 0);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `p` is zero, negative or even
+pub fn legendre<T>(a: T, p: T) -> i32 where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod {
+    jacobi(a, p)
+}
+
+#[allow(non_snake_case)]
+/// Computes the Kronecker symbol `(a/n)`, which extends the Jacobi symbol to
+/// every `n`
+///
+/// The factor `(a/2)` is `0` for even `a`, `1` for `a == ±1 (mod 8)` and `-1`
+/// for `a == ±3 (mod 8)`; `(a/-1)` is the sign of `a`; and `(a/0)` is `1` for
+/// `a == ±1` and `0` otherwise.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::kronecker;
+///
+/// assert_eq!(kronecker(5, 8), -1);
+/// assert_eq!(kronecker(-3i32, -12), 0);
+/// assert_eq!(kronecker(-5i32, -7), -1);
+/// assert_eq!(kronecker(1u8, 0), 1);
+/// ```
+pub fn kronecker<T>(a: T, n: T) -> i32 where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod {
+    let ONE: T = One::one();
+    let ZERO: T = Zero::zero();
+    let TWO = ONE + ONE;
+
+    if n.is_zero() {
+        return if a.is_one() || (a < ZERO && (a + ONE).is_zero()) { 1 } else { 0 };
+    }
+
+    let mut n = n;
+    let mut result = 1;
+    if n % TWO == ZERO {
+        if a % TWO == ZERO {
+            return 0;
+        }
+        // a is odd, so its residue modulo 8 is decided by the low three bits
+        let a_mod_8 = low_bits(a, 3);
+        while n % TWO == ZERO {
+            // Dividing rather than shifting keeps negative n exact
+            n = n / TWO;
+            if a_mod_8 == 3 || a_mod_8 == 5 {
+                result = -result;
+            }
+        }
+    }
+
+    // n is odd now, so negating it can't overflow
+    if n < ZERO {
+        n = ZERO - n;
+        if a < ZERO {
+            result = -result;
+        }
+    }
+    result * jacobi(a, n)
+}
+
+#[allow(non_snake_case)]
+/// The low `bits` bits of `x`, i.e. `x mod 2^bits` even for negative `x`
+fn low_bits<T>(x: T, bits: u32) -> u32 where T: Num + PartialOrd + Copy {
+    let ONE: T = One::one();
+    let TWO = ONE + ONE;
+
+    let mut x = x;
+    let mut value = 0;
+    for i in 0..bits {
+        // Of each remainder only its parity matters, which holds for negative
+        // x too
+        if !(x % TWO).is_zero() {
+            value |= 1 << i;
+            x = x - ONE;
+        }
+        x = x / TWO;
+    }
+    value
+}
+
+#[cfg(test)] mod tests {
+    use super::{jacobi, kronecker, legendre};
+    use {is_prime, mod_exp};
+
+    #[test]
+    fn test_legendre_matches_euler_criterion() {
+        for p in (3..400i64).filter(|&p| is_prime(p as u32)) {
+            for a in -400..400 {
+                let euler = match mod_exp(a, (p - 1) / 2, p) {
+                    0 => 0,
+                    1 => 1,
+                    r => { assert_eq!(r, p - 1); -1 }
+                };
+                assert_eq!(legendre(a, p), euler, "({}/{})", a, p);
+            }
+        }
+
+        let p = 0xffff_ffff_ffff_ffc5u64;
+        for &a in &[2, 3, 5, 0xdead_beef, p - 1, p + 2] {
+            let euler = mod_exp(a, (p - 1) / 2, p);
+            assert_eq!(legendre(a, p), if euler == 1 { 1 } else { -1 });
+        }
+    }
+
+    #[test]
+    fn test_jacobi_and_kronecker() {
+        // The Jacobi symbol is multiplicative in n
+        for n in (1..300u32).step_by(2) {
+            let factors: Vec<u32> = (3..=n).filter(|&p| is_prime(p) && n % p == 0).collect();
+            for a in 0..300u32 {
+                let mut expected = 1;
+                let mut m = n;
+                for &p in &factors {
+                    while m % p == 0 {
+                        expected *= legendre(a, p);
+                        m /= p;
+                    }
+                }
+                assert_eq!(jacobi(a, n), expected, "({}/{})", a, n);
+                assert_eq!(jacobi(a as i16 - 150, n as i16), jacobi((a + n * 150 - 150) % n, n));
+                assert_eq!(kronecker(a, n), expected);
+            }
+        }
+
+        assert_eq!(kronecker(3i8, i8::MIN), -1);
+        assert_eq!(kronecker(i64::MIN, 3), 1);
+        assert_eq!(kronecker(-1i32, 0), 1);
+        assert_eq!(kronecker(2u8, 0), 0);
+        assert_eq!(kronecker(7i32, -2), 1);
+        assert_eq!(kronecker(-7i32, -2), -1);
+        assert_eq!(kronecker(6u128, 10), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "jacobi: modulus is even")]
+    fn test_even_modulus_panics() {
+        jacobi(3, 10);
+    }
+}
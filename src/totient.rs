@@ -0,0 +1,467 @@
+use std::ops::Shr;
+#[cfg(feature = "bigint")]
+use num::{BigUint, ToPrimitive};
+use num::traits::{checked_pow, Num, NumCast, One, Zero};
+
+use error::ModExpError;
+use factor::factor;
+use gcd::gcd;
+use wide::WideningMulMod;
+use word::Word;
+use {check_modulus, checked_mod_exp, invert_negative_exponent, mod_exp, rem_euclid};
+
+/// Computes Euler's totient `phi(n)`, the number of integers in `[1, n]`
+/// coprime to `n`
+///
+/// Factors `n` with [`factor`]; use [`totient_from_factors`] when the
+/// factorization is already known.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::totient;
+///
+/// assert_eq!(totient(36), 12);
+/// assert_eq!(totient(97u64), 96);
+/// assert_eq!(totient(1u8), 1);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `n` is zero or negative
+pub fn totient<T>(n: T) -> T where T: Num + PartialOrd + Copy + NumCast {
+    totient_from_factors(&factor_positive(n, "totient"))
+}
+
+/// Computes Euler's totient from the factorization of `n` as
+/// `(prime, exponent)` pairs
+///
+/// `phi(n)` is the product of `q^(e-1) * (q - 1)` over the prime powers
+/// `q^e`. The primes must be distinct, which isn't checked.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::totient_from_factors;
+///
+/// assert_eq!(totient_from_factors(&[(2u32, 2), (3, 2)]), 12);
+/// ```
+pub fn totient_from_factors<T>(factors: &[(T, u32)]) -> T where T: Num + PartialOrd + Copy {
+    factors.iter().filter(|&&(_, e)| e > 0).fold(T::one(), |phi, &(q, e)| phi * prime_power_totient(q, e))
+}
+
+/// Computes Carmichael's function `lambda(n)`, the smallest `m > 0` with
+/// `a^m == 1 (mod n)` for every `a` coprime to `n`
+///
+/// `lambda(n)` divides `phi(n)` and is often much smaller, which makes it the
+/// better modulus for reducing exponents. Factors `n` with [`factor`]; use
+/// [`carmichael_from_factors`] when the factorization is already known.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::{carmichael, totient};
+///
+/// assert_eq!(carmichael(561), 80);
+/// assert_eq!(totient(561), 320);
+/// assert_eq!(carmichael(8u32), 2);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `n` is zero or negative
+pub fn carmichael<T>(n: T) -> T where T: Num + PartialOrd + Copy + NumCast {
+    carmichael_from_factors(&factor_positive(n, "carmichael"))
+}
+
+#[allow(non_snake_case)]
+/// Computes Carmichael's function from the factorization of `n` as
+/// `(prime, exponent)` pairs
+///
+/// `lambda(n)` is the least common multiple of `lambda(q^e)`, which equals
+/// `phi(q^e)` except for powers of two from 8 on, where it is half that. The
+/// primes must be distinct, which isn't checked.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::carmichael_from_factors;
+///
+/// assert_eq!(carmichael_from_factors(&[(3u64, 1), (11, 1), (17, 1)]), 80);
+/// assert_eq!(carmichael_from_factors(&[(2u64, 5)]), 8);
+/// ```
+pub fn carmichael_from_factors<T>(factors: &[(T, u32)]) -> T where T: Num + PartialOrd + Copy {
+    let ONE: T = One::one();
+    let TWO = ONE + ONE;
+
+    factors.iter().filter(|&&(_, e)| e > 0).fold(ONE, |lambda, &(q, e)| {
+        let mut term = prime_power_totient(q, e);
+        if q == TWO && e >= 3 {
+            term = term / TWO;
+        }
+        lambda / gcd(lambda, term) * term
+    })
+}
+
+/// Computes `base^exponent mod n`, first reducing the exponent modulo
+/// [`carmichael(n)`](carmichael)
+///
+/// For a base coprime to `n` the exponent can be reduced modulo `lambda(n)`
+/// outright. Otherwise powers of the primes shared with the base only settle
+/// to zero once the exponent reaches their multiplicity `k` in `n`, so an
+/// exponent of at least `k` is reduced to the smallest equivalent value that
+/// is still at least `k`. The result always matches [`mod_exp`](crate::mod_exp).
+///
+/// With the exponent in `T`, this never pays off: `mod_exp` needs at most one
+/// squaring per bit of `T`, which is less work than factoring `n` with
+/// [`factor`]. The reduction only saves time for exponents far wider than the
+/// modulus, which `mod_exp_reduced_big` takes as a `BigUint` when the
+/// `bigint` feature is enabled.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::{mod_exp, mod_exp_reduced};
+///
+/// assert_eq!(mod_exp_reduced(7u64, u64::MAX, 1000), mod_exp(7, u64::MAX, 1000));
+/// assert_eq!(mod_exp_reduced(2u64, 1 << 40, 24), 16);
+/// ```
+///
+/// # Panics
+///
+/// Panics whenever [`checked_mod_exp_reduced`] would return an error
+pub fn mod_exp_reduced<T>(base: T, exponent: T, n: T) -> T where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod + NumCast {
+    match checked_mod_exp_reduced(base, exponent, n) {
+        Ok(result) => result,
+        Err(e) => panic!("mod_exp_reduced: {}", e),
+    }
+}
+
+#[allow(non_snake_case)]
+/// Computes `base^exponent mod n` with the exponent reduced first, reporting
+/// invalid input instead of panicking
+///
+/// Fails in the same cases as [`checked_mod_exp`].
+pub fn checked_mod_exp_reduced<T>(base: T, exponent: T, n: T) -> Result<T, ModExpError> where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod + NumCast {
+    let ZERO: T = Zero::zero();
+
+    check_modulus(n)?;
+    if exponent < ZERO {
+        let (inverse, rest) = invert_negative_exponent(base, exponent, n)?;
+        let rest = checked_mod_exp_reduced(inverse, rest, n)?;
+        return Ok(rest.mul_mod(inverse, n));
+    }
+
+    let base = rem_euclid(base, n);
+    let factors = factor_positive(n, "mod_exp_reduced");
+    let exponent = reduce_exponent(exponent, carmichael_from_factors(&factors), threshold(base, &factors));
+    checked_mod_exp(base, exponent, n)
+}
+
+/// Computes `base^exponent mod n` for an arbitrary-precision exponent, first
+/// reducing it modulo [`carmichael(n)`](carmichael)
+///
+/// The reduction follows [`mod_exp_reduced`], and leaves an exponent below
+/// `lambda(n) + k` for one [`mod_exp`](crate::mod_exp) in `T`. That makes
+/// the cost one division of `exponent` plus factoring `n`, instead of a
+/// squaring for every bit of `exponent`.
+///
+/// # Examples
+///
+/// ```
+/// extern crate mod_exp;
+/// extern crate num;
+///
+/// use num::BigUint;
+///
+/// # fn main() {
+/// let googol = num::pow(BigUint::from(10u32), 100);
+/// assert_eq!(mod_exp::mod_exp_reduced_big(2u64, &googol, 1000), 376);
+/// # }
+/// ```
+///
+/// # Panics
+///
+/// Panics whenever [`checked_mod_exp_reduced_big`] would return an error
+#[cfg(feature = "bigint")]
+pub fn mod_exp_reduced_big<T>(base: T, exponent: &BigUint, n: T) -> T where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod + NumCast {
+    match checked_mod_exp_reduced_big(base, exponent, n) {
+        Ok(result) => result,
+        Err(e) => panic!("mod_exp_reduced_big: {}", e),
+    }
+}
+
+/// Computes `base^exponent mod n` for an arbitrary-precision exponent,
+/// reporting invalid input instead of panicking
+///
+/// Fails in the same cases as [`checked_mod_exp`], except that the exponent
+/// can't be negative.
+#[cfg(feature = "bigint")]
+pub fn checked_mod_exp_reduced_big<T>(base: T, exponent: &BigUint, n: T) -> Result<T, ModExpError> where T: Num + PartialOrd + Shr<T, Output=T> + Copy + WideningMulMod + NumCast {
+    check_modulus(n)?;
+    let base = rem_euclid(base, n);
+    let factors = factor_positive(n, "mod_exp_reduced_big");
+    let lambda = carmichael_from_factors(&factors);
+    let threshold = threshold(base, &factors);
+
+    // Both the reduced exponent and one below the threshold fit in T, as
+    // lambda(n) and every multiplicity in n do
+    let exponent = if *exponent < BigUint::from(threshold) {
+        T::from(exponent.to_u32().unwrap()).unwrap()
+    } else {
+        let reduced = exponent % BigUint::from(lambda.to_u128().unwrap());
+        raise_to_threshold(T::from(reduced.to_u128().unwrap()).unwrap(), lambda, threshold)
+    };
+    checked_mod_exp(base, exponent, n)
+}
+
+/// Computes `a^(b^(c^...)) mod m` for the tower `[a, b, c, ...]`
+///
+/// The exponents quickly outgrow every integer type, so the tower is walked
+/// with the generalized Euler theorem instead: once an exponent is at least
+/// the largest multiplicity `k` in `m` of any prime dividing the base, it only
+/// matters modulo [`carmichael(m)`](carmichael), and the rest of the tower is
+/// evaluated modulo that, recursively down the chain of Carmichael functions
+/// until it reaches one. Where the remaining tower is small enough to be
+/// computed exactly, as it must be to tell whether it is below `k`, it is.
+///
+/// Every level factors its modulus with [`factor`]. An empty tower is `1`
+/// and `0^0` is `1`, as for [`mod_exp`](crate::mod_exp).
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::tower_mod;
+///
+/// // 2^(3^4) = 2^81
+/// assert_eq!(tower_mod(&[2u64, 3, 4], 1000), 352);
+/// // The last ten digits of Graham's number, a tower of threes
+/// assert_eq!(tower_mod(&[3u64; 20], 10_000_000_000), 2464195387);
+/// ```
+///
+/// # Panics
+///
+/// Panics whenever [`checked_tower_mod`] would return an error
+pub fn tower_mod<T: Word>(tower: &[T], m: T) -> T {
+    match checked_tower_mod(tower, m) {
+        Ok(result) => result,
+        Err(e) => panic!("tower_mod: {}", e),
+    }
+}
+
+/// Computes `a^(b^(c^...)) mod m`, reporting invalid input instead of
+/// panicking
+///
+/// Fails with [`ModExpError::ZeroModulus`] or [`ModExpError::ModulusOne`] for
+/// `m` of zero or one.
+pub fn checked_tower_mod<T: Word>(tower: &[T], m: T) -> Result<T, ModExpError> {
+    check_modulus(m)?;
+    Ok(tower_residue(tower, m))
+}
+
+/// `tower mod m` for any positive `m`, including one
+fn tower_residue<T: Word>(tower: &[T], m: T) -> T {
+    let (base, rest) = match tower.split_first() {
+        Some((&base, rest)) => (base % m, rest),
+        None => return T::one() % m,
+    };
+    if m.is_one() || rest.is_empty() {
+        return base;
+    }
+    if let Some(exponent) = exact_tower(rest) {
+        return mod_exp(base, exponent, m);
+    }
+
+    // The exponent doesn't even fit in T, so it is above any threshold
+    let factors = factor_positive(m, "tower_mod");
+    let lambda = carmichael_from_factors(&factors);
+    let exponent = raise_to_threshold(tower_residue(rest, lambda), lambda, threshold(base, &factors));
+    mod_exp(base, exponent, m)
+}
+
+/// The exact value of the tower, or `None` if it overflows `T`
+fn exact_tower<T: Word>(tower: &[T]) -> Option<T> {
+    let (base, rest) = match tower.split_first() {
+        Some((&base, rest)) => (base, rest),
+        None => return Some(T::one()),
+    };
+    if base.is_one() {
+        return Some(base);
+    }
+    match exact_tower(rest) {
+        Some(exponent) if base.is_zero() => Some(if exponent.is_zero() { T::one() } else { base }),
+        // Any larger power of two or more overflows
+        Some(exponent) if exponent < T::from(T::BITS).unwrap() => checked_pow(base, exponent.to_usize().unwrap()),
+        Some(_) => None,
+        None if base.is_zero() => Some(base),
+        None => None,
+    }
+}
+
+/// Reduces `exponent` modulo `lambda` without going below `threshold`, the
+/// largest multiplicity in `n` of a prime that divides the base
+///
+/// Exponents below the threshold are returned as they are.
+fn reduce_exponent<T>(exponent: T, lambda: T, threshold: u32) -> T where T: Num + PartialOrd + Copy {
+    if is_below(exponent, threshold) {
+        return exponent;
+    }
+    raise_to_threshold(exponent % lambda, lambda, threshold)
+}
+
+/// Adds `lambda` to a reduced exponent until it reaches `threshold`, for an
+/// exponent known to be at least `threshold` before the reduction
+fn raise_to_threshold<T>(reduced: T, lambda: T, threshold: u32) -> T where T: Num + PartialOrd + Copy {
+    let mut reduced = reduced;
+    while is_below(reduced, threshold) {
+        reduced = reduced + lambda;
+    }
+    reduced
+}
+
+/// The largest multiplicity in `factors` of a prime that divides `base`, or
+/// zero when `base` is coprime to all of them
+fn threshold<T>(base: T, factors: &[(T, u32)]) -> u32 where T: Num + Copy {
+    factors.iter()
+        .filter(|&&(q, _)| (base % q).is_zero())
+        .map(|&(_, e)| e)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Whether `0 <= value < bound`
+fn is_below<T>(value: T, bound: u32) -> bool where T: Num + PartialOrd + Copy {
+    let mut value = value;
+    for _ in 0..bound {
+        if value.is_zero() {
+            return true;
+        }
+        value = value - T::one();
+    }
+    false
+}
+
+/// `phi(q^e) = q^(e-1) * (q - 1)` for a prime `q` and `e > 0`
+fn prime_power_totient<T>(q: T, e: u32) -> T where T: Num + Copy {
+    (1..e).fold(q - T::one(), |phi, _| phi * q)
+}
+
+/// Factors a positive `n` into `(prime, exponent)` pairs with [`factor`],
+/// panicking with `name` otherwise
+pub(crate) fn factor_positive<T>(n: T, name: &str) -> Vec<(T, u32)> where T: Num + PartialOrd + Copy + NumCast {
+    if n <= T::zero() {
+        panic!("{}: n is not positive", name);
+    }
+
+    // Factor in the narrowest unsigned type that holds n; every prime factor
+    // is at most n, so it converts back
+    let factors = match n.to_u64() {
+        Some(n) => factor(n).into_iter().map(|(p, e)| (p as u128, e)).collect(),
+        None => factor(n.to_u128().unwrap()),
+    };
+    factors.into_iter().map(|(p, e)| (T::from(p).unwrap(), e)).collect()
+}
+
+#[cfg(test)] mod tests {
+    use super::{carmichael, checked_mod_exp_reduced, checked_tower_mod, totient, tower_mod};
+    use {checked_mod_exp, mod_exp, ModExpError};
+
+    #[test]
+    fn test_totient_and_carmichael_match_brute_force() {
+        for n in 1..400u64 {
+            let coprime: Vec<u64> = (1..=n).filter(|&a| ::gcd::gcd(a, n) == 1).collect();
+            assert_eq!(totient(n), coprime.len() as u64, "phi({})", n);
+
+            let lambda = (1..=n).find(|&m| coprime.iter().all(|&a| n == 1 || mod_exp(a, m, n) == 1)).unwrap();
+            assert_eq!(carmichael(n), lambda, "lambda({})", n);
+            assert_eq!(carmichael(n as i32), lambda as i32);
+        }
+
+        assert_eq!(totient(u64::MAX), 18446744073709551615 / 3 * 2 / 5 * 4 / 17 * 16 / 257 * 256 / 641 * 640 / 65537 * 65536 / 6700417 * 6700416);
+        assert_eq!(carmichael(4294967291u32), 4294967290);
+        assert_eq!(totient(4294967291u64 * 4294967279), 18446743970630336620);
+    }
+
+    #[test]
+    fn test_mod_exp_reduced_matches_mod_exp() {
+        for n in 1..200i64 {
+            for base in -20..60 {
+                for exponent in (-5..50).chain(vec![i64::MAX, i64::MAX - 1, i64::MIN, 1 << 40]) {
+                    assert_eq!(checked_mod_exp_reduced(base, exponent, n), checked_mod_exp(base, exponent, n),
+                               "{}^{} mod {}", base, exponent, n);
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn test_mod_exp_reduced_big_matches_bigint_mod_exp() {
+        use num::BigUint;
+        use super::mod_exp_reduced_big;
+
+        let exponents: Vec<BigUint> = (0..12u32).map(BigUint::from)
+            .chain((1..6).map(|k| num::pow(BigUint::from(0x9e37_79b9_7f4a_7c15u64), k) + BigUint::from(k)))
+            .collect();
+        for n in 2..120u64 {
+            for base in 0..n.min(40) {
+                for exponent in &exponents {
+                    let expected = ::bigint::mod_exp(&BigUint::from(base), exponent, &BigUint::from(n));
+                    assert_eq!(BigUint::from(mod_exp_reduced_big(base, exponent, n)), expected, "{}^{} mod {}", base, exponent, n);
+                }
+            }
+        }
+
+        let p = 4294967291u128;
+        let exponent = num::pow(BigUint::from(u128::MAX), 8);
+        let expected = ::bigint::mod_exp(&BigUint::from(3u32), &exponent, &BigUint::from(p * p));
+        assert_eq!(BigUint::from(mod_exp_reduced_big(3u128, &exponent, p * p)), expected);
+        let expected = ::bigint::mod_exp(&BigUint::from(997u32), &exponent, &BigUint::from(1000u32));
+        assert_eq!(BigUint::from(mod_exp_reduced_big(-3i64, &exponent, 1000) as u64), expected);
+    }
+
+    #[test]
+    fn test_tower_mod_matches_nested_mod_exp() {
+        // Exact values of every tower [b, c] and [b, c, d] over 0..6 that fit
+        let mut tails = Vec::new();
+        for b in 0..6u64 {
+            for c in 0..6u32 {
+                tails.push((vec![b, c as u64], b.pow(c)));
+                for d in 0..6 {
+                    if let Some(value) = c.checked_pow(d).and_then(|e| b.checked_pow(e)) {
+                        tails.push((vec![b, c as u64, d as u64], value));
+                    }
+                }
+            }
+        }
+
+        for m in 2..150u64 {
+            for a in 0..12u64 {
+                assert_eq!(tower_mod(&[a], m), a % m);
+                for &(ref tail, value) in &tails {
+                    let mut tower = vec![a];
+                    tower.extend_from_slice(tail);
+                    assert_eq!(tower_mod(&tower, m), mod_exp(a, value, m), "{:?} mod {}", tower, m);
+                    // In u16 most of these exponents overflow, so they go
+                    // through the Carmichael reduction instead
+                    let narrow: Vec<u16> = tower.iter().map(|&x| x as u16).collect();
+                    assert_eq!(tower_mod(&narrow, m as u16) as u64, mod_exp(a, value, m), "{:?} mod {}", tower, m);
+                }
+            }
+        }
+
+        assert_eq!(tower_mod::<u32>(&[], 7), 1);
+        // 2^(2^65536), with lambda(255) = 16
+        assert_eq!(tower_mod(&[2u8, 2, 2, 2, 2, 2], 255), 1);
+        // 6^(5^262144), where the exponent is far past the threshold of 2^20
+        assert_eq!(tower_mod(&[6u64, 5, 4, 3, 2, 1, 0, 9], 1 << 20), 0);
+        assert_eq!(tower_mod(&[6u64, 5, 4, 3, 2, 1, 0, 9], 1_000_000), 109376);
+        assert_eq!(checked_tower_mod(&[2u64, 3], 1), Err(ModExpError::ModulusOne));
+    }
+
+    #[test]
+    #[should_panic(expected = "totient: n is not positive")]
+    fn test_totient_of_zero_panics() {
+        totient(0);
+    }
+}
@@ -0,0 +1,219 @@
+use montgomery::Montgomery;
+use multi::montgomery_multi_pow;
+use strategy::Strategy;
+
+/// Modular multiplication that cannot overflow
+///
+/// Implementations compute the full product of the two operands in a type
+/// twice as wide as `Self` before reducing it, so any modulus representable in
+/// `Self` can be used. `u8` through `u64` (and the signed types of the same
+/// widths) widen into the next larger primitive; `u128` and `i128` use a
+/// 128x128->256 bit multiply.
+///
+/// # Examples
+///
+/// ```
+/// use mod_exp::WideningMulMod;
+///
+/// assert_eq!(200u8.mul_mod(200, 251), 91);
+/// assert_eq!(u64::MAX.mul_mod(u64::MAX, 1 << 63), 1);
+/// ```
+pub trait WideningMulMod: Copy {
+    /// Returns `(self * rhs) % modulus`
+    ///
+    /// For signed types the remainder takes the sign of the product, like `%`.
+    fn mul_mod(self, rhs: Self, modulus: Self) -> Self;
+
+    /// Computes `self^exponent mod modulus` through a [`Montgomery`] context
+    /// when `modulus` is odd, or returns `None` to fall back to the plain loop
+    ///
+    /// Only called by `checked_mod_exp_with` once it has validated and
+    /// normalized its arguments, so `self` is in `[0, modulus)`, `exponent` is
+    /// non-negative and `modulus` is at least two.
+    #[doc(hidden)]
+    fn montgomery_pow(self, _exponent: Self, _modulus: Self, _strategy: Strategy) -> Option<Self> {
+        None
+    }
+
+    /// Computes the product of `bases[i]^exponents[i]` through a
+    /// [`Montgomery`] context when `modulus` is odd, or returns `None` to fall
+    /// back to plain multiplication
+    ///
+    /// The multi-exponentiation counterpart of `montgomery_pow`, called by
+    /// `checked_multi_mod_exp` with every base in `[0, modulus)` and every
+    /// exponent non-negative.
+    #[doc(hidden)]
+    fn montgomery_multi_pow(_bases: &[Self], _exponents: &[Self], _modulus: Self) -> Option<Self> {
+        None
+    }
+}
+
+macro_rules! widening_mul_mod_impl {
+    (@unsigned_pow $t:ty) => {
+        fn montgomery_pow(self, exponent: $t, modulus: $t, strategy: Strategy) -> Option<$t> {
+            if modulus & 1 == 1 {
+                Some(Montgomery::new(modulus).pow_with(strategy, self, exponent))
+            } else {
+                None
+            }
+        }
+
+        fn montgomery_multi_pow(bases: &[$t], exponents: &[$t], modulus: $t) -> Option<$t> {
+            if modulus & 1 == 1 {
+                Some(montgomery_multi_pow(&Montgomery::new(modulus), bases, exponents))
+            } else {
+                None
+            }
+        }
+    };
+    (@signed_pow $t:ty, $u:ty) => {
+        fn montgomery_pow(self, exponent: $t, modulus: $t, strategy: Strategy) -> Option<$t> {
+            if modulus & 1 == 0 {
+                return None;
+            }
+            let ctx = Montgomery::new(modulus as $u);
+            Some(ctx.pow_with(strategy, self as $u, exponent as $u) as $t)
+        }
+
+        fn montgomery_multi_pow(bases: &[$t], exponents: &[$t], modulus: $t) -> Option<$t> {
+            if modulus & 1 == 0 {
+                return None;
+            }
+            let bases: Vec<$u> = bases.iter().map(|&base| base as $u).collect();
+            let exponents: Vec<$u> = exponents.iter().map(|&exponent| exponent as $u).collect();
+            Some(montgomery_multi_pow(&Montgomery::new(modulus as $u), &bases, &exponents) as $t)
+        }
+    };
+    (unsigned $($t:ty => $wide:ty),*) => {$(
+        impl WideningMulMod for $t {
+            #[inline]
+            fn mul_mod(self, rhs: $t, modulus: $t) -> $t {
+                ((self as $wide * rhs as $wide) % modulus as $wide) as $t
+            }
+
+            widening_mul_mod_impl!(@unsigned_pow $t);
+        }
+    )*};
+    (signed $($t:ty => $wide:ty, $u:ty),*) => {$(
+        impl WideningMulMod for $t {
+            #[inline]
+            fn mul_mod(self, rhs: $t, modulus: $t) -> $t {
+                ((self as $wide * rhs as $wide) % modulus as $wide) as $t
+            }
+
+            widening_mul_mod_impl!(@signed_pow $t, $u);
+        }
+    )*};
+}
+
+widening_mul_mod_impl!(unsigned u8 => u16, u16 => u32, u32 => u64, u64 => u128, usize => u128);
+widening_mul_mod_impl!(signed i8 => i16, u8, i16 => i32, u16, i32 => i64, u32, i64 => i128, u64, isize => i128, usize);
+
+impl WideningMulMod for u128 {
+    #[inline]
+    fn mul_mod(self, rhs: u128, modulus: u128) -> u128 {
+        let (lo, hi) = mul_u128(self, rhs);
+        rem_u256(lo, hi, modulus)
+    }
+
+    fn montgomery_pow(self, exponent: u128, modulus: u128, strategy: Strategy) -> Option<u128> {
+        if modulus & 1 == 0 {
+            return None;
+        }
+        // Half width arithmetic is much cheaper when everything fits, which is
+        // the common case for the prime factors behind mod_exp_crt
+        if modulus <= LOW_64 && exponent <= LOW_64 {
+            let ctx = Montgomery::new(modulus as u64);
+            return Some(ctx.pow_with(strategy, self as u64, exponent as u64) as u128);
+        }
+        Some(Montgomery::new(modulus).pow_with(strategy, self, exponent))
+    }
+
+    fn montgomery_multi_pow(bases: &[u128], exponents: &[u128], modulus: u128) -> Option<u128> {
+        if modulus & 1 == 0 {
+            return None;
+        }
+        if modulus <= LOW_64 {
+            let bases: Vec<u64> = bases.iter().map(|&base| base as u64).collect();
+            return Some(montgomery_multi_pow(&Montgomery::new(modulus as u64), &bases, exponents) as u128);
+        }
+        Some(montgomery_multi_pow(&Montgomery::new(modulus), bases, exponents))
+    }
+}
+
+impl WideningMulMod for i128 {
+    #[inline]
+    fn mul_mod(self, rhs: i128, modulus: i128) -> i128 {
+        let r = self.unsigned_abs().mul_mod(rhs.unsigned_abs(), modulus.unsigned_abs()) as i128;
+        if (self < 0) != (rhs < 0) { -r } else { r }
+    }
+
+    widening_mul_mod_impl!(@signed_pow i128, u128);
+}
+
+const LOW_64: u128 = (1 << 64) - 1;
+
+/// Full 256 bit product of two `u128`s, as `(low, high)` halves
+pub(crate) fn mul_u128(a: u128, b: u128) -> (u128, u128) {
+    let (a0, a1) = (a & LOW_64, a >> 64);
+    let (b0, b1) = (b & LOW_64, b >> 64);
+
+    let p00 = a0 * b0;
+    let p01 = a0 * b1;
+    let p10 = a1 * b0;
+    let p11 = a1 * b1;
+
+    let mid = (p00 >> 64) + (p01 & LOW_64) + (p10 & LOW_64);
+    let lo = (mid << 64) | (p00 & LOW_64);
+    let hi = p11 + (p01 >> 64) + (p10 >> 64) + (mid >> 64);
+    (lo, hi)
+}
+
+/// Remainder of the 256 bit value `hi * 2^128 + lo` modulo `m`
+pub(crate) fn rem_u256(lo: u128, hi: u128, m: u128) -> u128 {
+    if m <= LOW_64 {
+        // Each step shifts a remainder below 2^64 up by 64 bits, which fits.
+        let r = hi % m;
+        let r = ((r << 64) | (lo >> 64)) % m;
+        return ((r << 64) | (lo & LOW_64)) % m;
+    }
+
+    let mut r = hi % m;
+    for i in (0..128).rev() {
+        r = add_mod_u128(r, r, m);
+        if (lo >> i) & 1 == 1 {
+            r = add_mod_u128(r, 1, m);
+        }
+    }
+    r
+}
+
+/// `(a + b) % m` for `a, b < m`, without overflowing
+fn add_mod_u128(a: u128, b: u128, m: u128) -> u128 {
+    let (sum, carry) = a.overflowing_add(b);
+    if carry || sum >= m { sum.wrapping_sub(m) } else { sum }
+}
+
+#[cfg(test)] mod tests {
+    use super::{mul_u128, WideningMulMod};
+
+    #[test]
+    fn test_mul_u128() {
+        assert_eq!(mul_u128(u128::MAX, u128::MAX), (1, u128::MAX - 1));
+        assert_eq!(mul_u128(1 << 127, 4), (0, 2));
+    }
+
+    #[test]
+    fn test_mul_mod_matches_wide_product() {
+        for &(a, b, m) in &[(250u8, 251u8, 255u8), (3, 7, 2), (255, 255, 254)] {
+            let expected = (a as u32 * b as u32 % m as u32) as u8;
+            assert_eq!(a.mul_mod(b, m), expected);
+            assert_eq!((a as u128).mul_mod(b as u128, m as u128), expected as u128);
+        }
+
+        let m = u128::MAX - 158;
+        assert_eq!((m - 1).mul_mod(m - 1, m), 1);
+        assert_eq!((-3i128).mul_mod(5, 7), -1);
+        assert_eq!(i128::MIN.mul_mod(i128::MIN, i128::MAX), 1);
+    }
+}
@@ -0,0 +1,107 @@
+use std::ops::Shr;
+use num::traits::{checked_pow, PrimInt, Unsigned};
+
+use wide::{mul_u128, WideningMulMod};
+
+/// An unsigned primitive integer the reduction contexts can work in
+///
+/// Implemented for `u8`, `u16`, `u32`, `u64`, `u128` and `usize`. The methods
+/// mirror the inherent methods of the same names, plus a double-width product.
+pub trait Word: PrimInt + Unsigned + WideningMulMod + Shr<Self, Output=Self> {
+    /// Width of the type in bits
+    const BITS: u32;
+
+    /// Full product of `self * rhs`, as `(low, high)` halves
+    fn widening_mul(self, rhs: Self) -> (Self, Self);
+
+    /// `self + rhs`, wrapping around at the boundary of the type
+    fn wrapping_add(self, rhs: Self) -> Self;
+
+    /// `self - rhs`, wrapping around at the boundary of the type
+    fn wrapping_sub(self, rhs: Self) -> Self;
+
+    /// `self * rhs`, wrapping around at the boundary of the type
+    fn wrapping_mul(self, rhs: Self) -> Self;
+
+    /// `self + rhs`, along with whether the addition overflowed
+    fn overflowing_add(self, rhs: Self) -> (Self, bool);
+
+    /// `self - rhs`, along with whether the subtraction overflowed
+    fn overflowing_sub(self, rhs: Self) -> (Self, bool);
+}
+
+macro_rules! word_impl {
+    (@ops $t:ty) => {
+        #[inline]
+        fn wrapping_add(self, rhs: $t) -> $t { <$t>::wrapping_add(self, rhs) }
+        #[inline]
+        fn wrapping_sub(self, rhs: $t) -> $t { <$t>::wrapping_sub(self, rhs) }
+        #[inline]
+        fn wrapping_mul(self, rhs: $t) -> $t { <$t>::wrapping_mul(self, rhs) }
+        #[inline]
+        fn overflowing_add(self, rhs: $t) -> ($t, bool) { <$t>::overflowing_add(self, rhs) }
+        #[inline]
+        fn overflowing_sub(self, rhs: $t) -> ($t, bool) { <$t>::overflowing_sub(self, rhs) }
+    };
+    ($($t:ty => $wide:ty),*) => {$(
+        impl Word for $t {
+            const BITS: u32 = <$t>::BITS;
+
+            #[inline]
+            fn widening_mul(self, rhs: $t) -> ($t, $t) {
+                let product = self as $wide * rhs as $wide;
+                (product as $t, (product >> <$t>::BITS) as $t)
+            }
+
+            word_impl!(@ops $t);
+        }
+    )*};
+}
+
+word_impl!(u8 => u16, u16 => u32, u32 => u64, u64 => u128, usize => u128);
+
+impl Word for u128 {
+    const BITS: u32 = 128;
+
+    #[inline]
+    fn widening_mul(self, rhs: u128) -> (u128, u128) {
+        mul_u128(self, rhs)
+    }
+
+    word_impl!(@ops u128);
+}
+
+/// `floor(sqrt(n))`, by Newton's iteration from above
+pub(crate) fn isqrt<T: Word>(n: T) -> T {
+    if n.is_zero() {
+        return n;
+    }
+    let bits = T::BITS - n.leading_zeros();
+    let mut x = T::one() << bits.div_ceil(2) as usize;
+    loop {
+        let y = (x + n / x) >> 1;
+        if y >= x {
+            return x;
+        }
+        x = y;
+    }
+}
+
+/// `floor(n^(1/k))` for `k >= 1`, by Newton's iteration from above
+pub(crate) fn iroot<T: Word>(n: T, k: u32) -> T {
+    if n.is_zero() || k == 1 {
+        return n;
+    }
+    let bits = T::BITS - n.leading_zeros();
+    let k_minus_one = T::from(k - 1).unwrap();
+    let mut x = T::one() << bits.div_ceil(k) as usize;
+    loop {
+        // An overflowing x^(k-1) is above n, so the quotient is zero
+        let quotient = checked_pow(x, k as usize - 1).map_or(T::zero(), |power| n / power);
+        let y = (x * k_minus_one + quotient) / (k_minus_one + T::one());
+        if y >= x {
+            return x;
+        }
+        x = y;
+    }
+}